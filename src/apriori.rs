@@ -1,22 +1,491 @@
-use std::collections::HashSet;
-use std::fs::read_to_string;
+use crate::parallel::map_indices;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
 
-pub fn read_file(filepath: &str) -> Vec<HashSet<String>> {
-    let output = read_to_string(filepath).expect("Something went wrong reading the file");
+/// Layout of a transaction file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// One row per basket, every field being a product.
+    Basket,
+    /// One `transaction_id,item` row per product, rows of a transaction need not be adjacent.
+    Long,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Csv(csv::Error),
+    /// A long-format or sequence row lacking one of its fields.
+    MissingField {
+        line: u64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Csv(e) => write!(f, "could not read transactions: {}", e),
+            Error::MissingField { line } => write!(f, "missing a field on line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Csv(e) => Some(e),
+            Error::MissingField { .. } => None,
+        }
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Error {
+        Error::Csv(e)
+    }
+}
+
+/// Reads transactions from a CSV file.
+///
+/// Fields may be quoted and are trimmed, lines may end in CRLF, and blank lines or baskets without
+/// any products are skipped. When `has_header` is set the first row is ignored.
+pub fn read_file(filepath: &str, format: Format, has_header: bool) -> Result<TransactionDb, Error> {
+    let reader = csv_reader(has_header).from_path(filepath)?;
+    read_records(reader, format)
+}
+
+/// Reads transactions like [`read_file`], from any reader.
+pub fn read_reader<R: io::Read>(reader: R, format: Format, has_header: bool) -> Result<TransactionDb, Error> {
+    read_records(csv_reader(has_header).from_reader(reader), format)
+}
+
+fn csv_reader(has_header: bool) -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder.has_headers(has_header).flexible(true).trim(csv::Trim::All);
+    builder
+}
+
+fn read_records<R: io::Read>(mut reader: csv::Reader<R>, format: Format) -> Result<TransactionDb, Error> {
+    let mut db = TransactionDb::default();
+
+    match format {
+        Format::Basket => {
+            for record in reader.records() {
+                let record = record?;
+                let basket: Vec<&str> = record.iter().filter(|p| !p.is_empty()).collect();
+                if !basket.is_empty() {
+                    db.push(basket);
+                }
+            }
+        }
+        Format::Long => {
+            // Transactions keep the order in which their ids first appear
+            let mut positions: HashMap<String, usize> = HashMap::new();
+            let mut baskets: Vec<Vec<String>> = Vec::new();
+            for record in reader.records() {
+                let record = record?;
+                if record.iter().all(str::is_empty) {
+                    continue; // A row of only empty fields
+                }
+                let line = record.position().map_or(0, |p| p.line());
+                let (id, item) = match (record.get(0), record.get(1)) {
+                    (Some(id), Some(item)) if !id.is_empty() && !item.is_empty() => (id, item),
+                    _ => return Err(Error::MissingField { line }),
+                };
+                let position = *positions.entry(id.to_string()).or_insert_with(|| {
+                    baskets.push(Vec::new());
+                    baskets.len() - 1
+                });
+                baskets[position].push(item.to_string());
+            }
+            baskets.into_iter().for_each(|basket| db.push(basket));
+        }
+    }
+    Ok(db)
+}
+
+/// Reads a sequence database from a CSV file of `customer_id,transaction_id,item` rows.
+///
+/// Each customer's transactions are ordered by their first appearance in the file, so rows should
+/// be sorted chronologically. Parsing follows [`read_file`].
+pub fn read_sequences(filepath: &str, has_header: bool) -> Result<SequenceDb, Error> {
+    let reader = csv_reader(has_header).from_path(filepath)?;
+    read_sequence_records(reader)
+}
+
+/// Reads a sequence database like [`read_sequences`], from any reader.
+pub fn read_sequences_reader<R: io::Read>(reader: R, has_header: bool) -> Result<SequenceDb, Error> {
+    read_sequence_records(csv_reader(has_header).from_reader(reader))
+}
+
+fn read_sequence_records<R: io::Read>(mut reader: csv::Reader<R>) -> Result<SequenceDb, Error> {
+    let mut transactions: Vec<(String, Vec<String>)> = Vec::new();
+    let mut positions: HashMap<(String, String), usize> = HashMap::new();
+
+    for record in reader.records() {
+        let record = record?;
+        if record.iter().all(str::is_empty) {
+            continue; // A row of only empty fields
+        }
+        let line = record.position().map_or(0, |p| p.line());
+        let fields: Vec<&str> = record.iter().collect();
+        let (customer, transaction, item) = match fields.as_slice() {
+            [customer, transaction, item, ..] if !customer.is_empty() && !transaction.is_empty() && !item.is_empty() => {
+                (*customer, *transaction, *item)
+            }
+            _ => return Err(Error::MissingField { line }),
+        };
+        let key = (customer.to_string(), transaction.to_string());
+        let position = *positions.entry(key).or_insert_with(|| {
+            transactions.push((customer.to_string(), Vec::new()));
+            transactions.len() - 1
+        });
+        transactions[position].1.push(item.to_string());
+    }
+
+    let mut db = SequenceDb::default();
+    for (customer, itemset) in transactions {
+        db.push(&customer, itemset);
+    }
+    Ok(db)
+}
+
+/// A frequent itemset, its items sorted, together with its support.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Itemset {
+    pub items: Vec<String>,
+    pub support: f32,
+}
+
+/// An association rule `antecedent -> consequent` together with its interestingness measures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub antecedent: Vec<String>,
+    pub consequent: Vec<String>,
+    pub support: f32,
+    pub confidence: f32,
+    pub lift: f32,
+    pub leverage: f32,
+    pub conviction: f32,
+}
+
+/// Measure used to rank rules and recommendations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Support,
+    Confidence,
+    Lift,
+    Leverage,
+    Conviction,
+}
+
+impl Rule {
+    fn new(antecedent: Vec<String>, consequent: Vec<String>, support: f32, support_a: f32, support_c: f32) -> Rule {
+        let confidence = support / support_a;
+        let conviction = if confidence >= 1. {
+            f32::INFINITY
+        } else {
+            (1. - support_c) / (1. - confidence)
+        };
+        Rule {
+            antecedent,
+            consequent,
+            support,
+            confidence,
+            lift: confidence / support_c,
+            leverage: support - support_a * support_c,
+            conviction,
+        }
+    }
+
+    pub fn metric(&self, metric: Metric) -> f32 {
+        match metric {
+            Metric::Support => self.support,
+            Metric::Confidence => self.confidence,
+            Metric::Lift => self.lift,
+            Metric::Leverage => self.leverage,
+            Metric::Conviction => self.conviction,
+        }
+    }
+}
+
+/// Transactions with their products interned to integer ids.
+///
+/// Every product is stored vertically as a bitset over the transactions containing it, so the
+/// support of an itemset is counted with a bitwise AND and a popcount rather than string hashing.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionDb {
+    products: Vec<String>,
+    ids: HashMap<String, usize>,
+    bitsets: Vec<Vec<u64>>,
+    n_transactions: usize,
+}
+
+impl TransactionDb {
+    pub fn new(transactions: &[HashSet<String>]) -> TransactionDb {
+        let mut db = TransactionDb::default();
+        for t in transactions.iter() {
+            db.push(t);
+        }
+        db
+    }
 
-    let transactions: Vec<&str> = output.split("\n").collect();
-    let mut cleaned_transactions = Vec::new();
-    // Skip first line containing all products
-    for t in transactions.iter().skip(1) {
-        if t == &"" {
-            break; // Skip empty transactions
+    /// Appends a transaction, interning any products not seen before.
+    pub fn push<I, S>(&mut self, transaction: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let n_words = self.n_transactions / 64 + 1;
+        if n_words > self.bitsets.first().map_or(0, |b| b.len()) {
+            self.bitsets.iter_mut().for_each(|b| b.push(0));
         }
-        let set = t.split(",").collect::<HashSet<&str>>();
-        let set = set.iter().map(|x| x.to_string()).collect();
-        cleaned_transactions.push(set);
+
+        for product in transaction {
+            let product = product.as_ref();
+            let id = match self.ids.get(product) {
+                Some(&id) => id,
+                None => {
+                    let id = self.products.len();
+                    self.products.push(product.to_string());
+                    self.ids.insert(product.to_string(), id);
+                    self.bitsets.push(vec![0; n_words]);
+                    id
+                }
+            };
+            self.bitsets[id][self.n_transactions / 64] |= 1 << (self.n_transactions % 64);
+        }
+        self.n_transactions += 1;
+    }
+
+    /// Number of transactions.
+    pub fn len(&self) -> usize {
+        self.n_transactions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_transactions == 0
+    }
+
+    /// Product names, indexed by id.
+    pub fn products(&self) -> &[String] {
+        &self.products
+    }
+
+    pub fn id(&self, product: &str) -> Option<usize> {
+        self.ids.get(product).copied()
+    }
+
+    /// Decodes the transactions back into sets of product names.
+    pub fn transactions(&self) -> Vec<HashSet<String>> {
+        (0..self.n_transactions)
+            .map(|t| {
+                self.bitsets
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, bitset)| bitset[t / 64] & (1 << (t % 64)) != 0)
+                    .map(|(id, _)| self.products[id].clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// One row per transaction with a 1 for every product it contains, columns ordered as `products`.
+    pub fn to_matrix(&self) -> Vec<Vec<f32>> {
+        (0..self.n_transactions)
+            .map(|t| self.bitsets.iter().map(|bitset| ((bitset[t / 64] >> (t % 64)) & 1) as f32).collect())
+            .collect()
+    }
+
+    /// Number of transactions containing every product id in `ids`.
+    pub fn count(&self, ids: &[usize]) -> usize {
+        match ids.split_first() {
+            None => self.n_transactions,
+            Some((first, rest)) => self.bitsets[*first]
+                .iter()
+                .enumerate()
+                .map(|(w, word)| rest.iter().fold(*word, |acc, id| acc & self.bitsets[*id][w]).count_ones() as usize)
+                .sum(),
+        }
+    }
+
+    pub fn support(&self, products: &HashSet<String>) -> f32 {
+        let ids: Option<Vec<usize>> = products.iter().map(|p| self.id(p)).collect();
+        match ids {
+            Some(ids) => self.count(&ids) as f32 / self.n_transactions as f32,
+            None => 0., // A product that never occurs
+        }
+    }
+
+    pub fn confidence(&self, products_a: &HashSet<String>, products_b: &HashSet<String>) -> f32 {
+        let prod_union = products_a.union(products_b).cloned().collect();
+        self.support(&prod_union) / self.support(products_a)
+    }
+
+    pub fn lift(&self, products_a: &HashSet<String>, products_b: &HashSet<String>) -> f32 {
+        self.confidence(products_a, products_b) / self.support(products_b)
+    }
+
+    /// Support where each transaction counts with its weight, such as the value of the basket.
+    pub fn weighted_support(&self, products: &HashSet<String>, weights: &[f32]) -> f32 {
+        if weights.len() != self.n_transactions {
+            panic!(
+                "Transactions and weights have different lengths, got {} and {}",
+                self.n_transactions,
+                weights.len()
+            );
+        }
+        let ids: Option<Vec<usize>> = products.iter().map(|p| self.id(p)).collect();
+        match ids {
+            Some(ids) => self.weight(&ids, weights) / weights.iter().sum::<f32>(),
+            None => 0., // A product that never occurs
+        }
+    }
+
+    pub fn weighted_confidence(&self, products_a: &HashSet<String>, products_b: &HashSet<String>, weights: &[f32]) -> f32 {
+        let prod_union = products_a.union(products_b).cloned().collect();
+        self.weighted_support(&prod_union, weights) / self.weighted_support(products_a, weights)
+    }
+
+    pub fn weighted_lift(&self, products_a: &HashSet<String>, products_b: &HashSet<String>, weights: &[f32]) -> f32 {
+        self.weighted_confidence(products_a, products_b, weights) / self.weighted_support(products_b, weights)
+    }
+
+    // Summed weight of the transactions containing every product id in `ids`.
+    fn weight(&self, ids: &[usize], weights: &[f32]) -> f32 {
+        match ids.split_first() {
+            None => weights.iter().sum(),
+            Some((first, rest)) => self.bitsets[*first]
+                .iter()
+                .enumerate()
+                .map(|(w, word)| {
+                    let mut word = rest.iter().fold(*word, |acc, id| acc & self.bitsets[*id][w]);
+                    let mut weight = 0.;
+                    while word != 0 {
+                        weight += weights[w * 64 + word.trailing_zeros() as usize];
+                        word &= word - 1;
+                    }
+                    weight
+                })
+                .sum(),
+        }
+    }
+}
+
+/// Ordered transactions per customer, with products interned to integer ids.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceDb {
+    products: Vec<String>,
+    ids: HashMap<String, usize>,
+    customers: Vec<String>,
+    positions: HashMap<String, usize>,
+    sequences: Vec<Vec<Vec<usize>>>,
+}
+
+/// A frequent sequence of itemsets, each itemset sorted, together with its support.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequentialPattern {
+    pub sequence: Vec<Vec<String>>,
+    pub support: f32,
+}
+
+impl SequenceDb {
+    pub fn new() -> SequenceDb {
+        SequenceDb::default()
+    }
+
+    /// Appends a transaction to the end of a customer's sequence.
+    pub fn push<I, S>(&mut self, customer: &str, transaction: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut itemset: Vec<usize> = transaction
+            .into_iter()
+            .map(|product| {
+                let product = product.as_ref();
+                match self.ids.get(product) {
+                    Some(&id) => id,
+                    None => {
+                        self.products.push(product.to_string());
+                        self.ids.insert(product.to_string(), self.products.len() - 1);
+                        self.products.len() - 1
+                    }
+                }
+            })
+            .collect();
+        itemset.sort_unstable();
+        itemset.dedup();
+        if itemset.is_empty() {
+            return;
+        }
+
+        let position = match self.positions.get(customer) {
+            Some(&position) => position,
+            None => {
+                self.customers.push(customer.to_string());
+                self.positions.insert(customer.to_string(), self.sequences.len());
+                self.sequences.push(Vec::new());
+                self.sequences.len() - 1
+            }
+        };
+        self.sequences[position].push(itemset);
+    }
+
+    /// Number of customers.
+    pub fn len(&self) -> usize {
+        self.sequences.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sequences.is_empty()
+    }
+
+    pub fn customers(&self) -> &[String] {
+        &self.customers
+    }
+
+    /// Decodes a customer's sequence back into sets of product names.
+    pub fn sequence(&self, customer: &str) -> Option<Vec<HashSet<String>>> {
+        self.positions.get(customer).map(|&position| {
+            self.sequences[position]
+                .iter()
+                .map(|itemset| itemset.iter().map(|&id| self.products[id].clone()).collect())
+                .collect()
+        })
     }
 
-    cleaned_transactions
+    /// Fraction of customers whose sequence contains `pattern`, each itemset of the pattern
+    /// occurring within a single transaction later than the one before it.
+    pub fn support(&self, pattern: &[HashSet<String>]) -> f32 {
+        let pattern: Option<Vec<Vec<usize>>> = pattern
+            .iter()
+            .map(|itemset| itemset.iter().map(|p| self.ids.get(p).copied()).collect())
+            .collect();
+        let pattern = match pattern {
+            Some(pattern) => pattern,
+            None => return 0., // A product that never occurs
+        };
+
+        let count = self
+            .sequences
+            .iter()
+            .filter(|sequence| {
+                // Matching every itemset as early as possible leaves the most room for the rest
+                let mut transactions = sequence.iter();
+                pattern
+                    .iter()
+                    .all(|itemset| transactions.any(|t| itemset.iter().all(|id| t.binary_search(id).is_ok())))
+            })
+            .count();
+        count as f32 / self.sequences.len() as f32
+    }
+
+    /// Confidence that a customer whose sequence contains `prefix` later follows it with `suffix`.
+    pub fn confidence(&self, prefix: &[HashSet<String>], suffix: &[HashSet<String>]) -> f32 {
+        let pattern: Vec<HashSet<String>> = prefix.iter().chain(suffix).cloned().collect();
+        self.support(&pattern) / self.support(prefix)
+    }
 }
 
 pub fn support(products: HashSet<String>, transactions: &Vec<HashSet<String>>) -> f32 {
@@ -44,42 +513,571 @@ pub fn lift(
     transactions: &Vec<HashSet<String>>,
 ) -> f32 {
     let products_a_ = products_a.iter().cloned().collect();
-    let products_b_ = products_a.iter().cloned().collect();
+    let products_b_ = products_b.iter().cloned().collect();
     let prod_union = products_a.into_iter().chain(products_b).collect();
     support(prod_union, &transactions)
         / (support(products_a_, &transactions) * support(products_b_, &transactions))
 }
 
-pub fn best_lift(
-    given_product: HashSet<String>,
-    transactions: &Vec<HashSet<String>>,
-) -> (f32, HashSet<String>) {
-    let mut unique_products = HashSet::new();
-    let mut max_lift: f32 = 0.0;
-    let mut max_prod = HashSet::new();
+/// An itemset, its items sorted, together with its total utility.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtilityItemset {
+    pub items: Vec<String>,
+    pub utility: f32,
+}
+
+/// Total utility of `products` in the transactions containing all of them, where every transaction
+/// maps its products to their utility, such as quantity times price.
+pub fn utility(products: &HashSet<String>, transactions: &[HashMap<String, f32>]) -> f32 {
+    transactions
+        .iter()
+        .filter_map(|t| products.iter().map(|p| t.get(p)).sum::<Option<f32>>())
+        .sum()
+}
+
+/// Mines all itemsets with a total utility of at least `min_utility` with the Two-Phase algorithm.
+///
+/// Utility is not anti-monotone, so candidates are first generated level by level on their
+/// transaction-weighted utility, the summed utility of the whole transactions containing them,
+/// which bounds the utility of every superset. Their exact utility is computed afterwards.
+/// Itemsets are returned ordered by size, then lexicographically.
+pub fn high_utility_itemsets(transactions: &[HashMap<String, f32>], min_utility: f32) -> Vec<UtilityItemset> {
+    let transaction_utilities: Vec<f32> = transactions.iter().map(|t| t.values().sum()).collect();
+    let twu = |items: &[String]| -> f32 {
+        transactions
+            .iter()
+            .zip(transaction_utilities.iter())
+            .filter(|(t, _)| items.iter().all(|p| t.contains_key(p)))
+            .map(|(_, u)| u)
+            .sum()
+    };
+
+    let mut unique_products: Vec<String> = transactions
+        .iter()
+        .flat_map(|t| t.keys())
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    unique_products.sort();
+
+    let mut candidates: Vec<Vec<String>> = unique_products.into_iter().map(|p| vec![p]).collect();
+    let mut high_utility = Vec::new();
+
+    while !candidates.is_empty() {
+        let level: Vec<Vec<String>> = candidates.into_iter().filter(|items| twu(items) >= min_utility).collect();
+        candidates = generate_candidates(&level.iter().map(|items| items.as_slice()).collect::<Vec<_>>());
 
-    for t in transactions {
-        // Rewrite to reduce with union operator
-        unique_products.extend(t);
+        for items in level {
+            let utility = utility(&items.iter().cloned().collect(), transactions);
+            if utility >= min_utility {
+                high_utility.push(UtilityItemset { items, utility });
+            }
+        }
+    }
+
+    high_utility.sort_by(|a, b| a.items.len().cmp(&b.items.len()).then_with(|| a.items.cmp(&b.items)));
+    high_utility
+}
+
+/// Finds the single product with the highest lift towards `given_product`, which may hold any
+/// number of products. Returns a lift of zero and no product when nothing co-occurs with it.
+pub fn best_lift(given_product: HashSet<String>, transactions: &[HashSet<String>]) -> (f32, HashSet<String>) {
+    let db = TransactionDb::new(transactions);
+    match recommend(&given_product, &db, 1, Metric::Lift).pop() {
+        Some((product, lift)) => (lift, std::iter::once(product).collect()),
+        None => (0., HashSet::new()),
+    }
+}
+
+/// Recommends the `k` products that best complement `basket`, best first.
+///
+/// Every product outside the basket that co-occurs with it is scored as the consequent of the rule
+/// `basket -> product`, using `metric`. Ties are broken alphabetically.
+pub fn recommend(basket: &HashSet<String>, transactions: &TransactionDb, k: usize, metric: Metric) -> Vec<(String, f32)> {
+    let basket_ids: Option<Vec<usize>> = basket.iter().map(|p| transactions.id(p)).collect();
+    let mut basket_ids = match basket_ids {
+        Some(ids) => ids,
+        None => return Vec::new(), // The basket never occurs, so nothing co-occurs with it
+    };
+    let basket_count = transactions.count(&basket_ids);
+    if basket_count == 0 {
+        return Vec::new();
     }
 
-    let given_product_str: Vec<_> = given_product.iter().cloned().collect();
-    unique_products.remove(&given_product_str[0]); // Only works for given_product with 1 element
+    let n_transactions = transactions.len() as f32;
+    let mut antecedent: Vec<String> = basket.iter().cloned().collect();
+    antecedent.sort();
 
-    for tr_product in unique_products.iter() {
-        let mut set_product = HashSet::new();
-        set_product.insert(tr_product.to_string());
+    let mut scores: Vec<(String, f32)> = Vec::new();
+    for (id, product) in transactions.products().iter().enumerate() {
+        if basket.contains(product) {
+            continue;
+        }
+        basket_ids.push(id);
+        let joint_count = transactions.count(&basket_ids);
+        basket_ids.pop();
+        if joint_count == 0 {
+            continue;
+        }
 
-        let current_lift = lift(
-            given_product.iter().cloned().collect(),
-            set_product.iter().cloned().collect(),
-            transactions,
+        let rule = Rule::new(
+            antecedent.clone(),
+            vec![product.clone()],
+            joint_count as f32 / n_transactions,
+            basket_count as f32 / n_transactions,
+            transactions.count(&[id]) as f32 / n_transactions,
         );
+        scores.push((product.clone(), rule.metric(metric)));
+    }
+
+    scores.sort_by(|(p_a, a), (p_b, b)| b.partial_cmp(a).unwrap().then_with(|| p_a.cmp(p_b)));
+    scores.truncate(k);
+    scores
+}
+
+/// Mines all itemsets with at least `min_support` using the Apriori algorithm.
+///
+/// Candidates of size k + 1 are joined from frequent k-itemsets sharing their first k - 1 items
+/// and pruned when any of their k-subsets is infrequent (downward closure), before being counted.
+/// Itemsets are returned ordered by size, then lexicographically.
+pub fn mine(transactions: &[HashSet<String>], min_support: f32) -> Vec<Itemset> {
+    let n_transactions = transactions.len();
+    let min_count = min_count(min_support, n_transactions);
 
-        if current_lift >= max_lift {
-            max_lift = current_lift;
-            max_prod = set_product.iter().cloned().collect();
+    let mut unique_products: Vec<String> = transactions.iter().flatten().cloned().collect::<HashSet<_>>().into_iter().collect();
+    unique_products.sort();
+
+    let mut candidates: Vec<Vec<String>> = unique_products.into_iter().map(|p| vec![p]).collect();
+    let mut frequent = Vec::new();
+
+    while !candidates.is_empty() {
+        let level: Vec<Itemset> = candidates
+            .into_iter()
+            .filter_map(|items| {
+                let count = transactions.iter().filter(|t| items.iter().all(|p| t.contains(p))).count();
+                if count >= min_count {
+                    Some(Itemset {
+                        items,
+                        support: count as f32 / n_transactions as f32,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        candidates = generate_candidates(&level.iter().map(|s| s.items.as_slice()).collect::<Vec<_>>());
+        frequent.extend(level);
+    }
+
+    sort_itemsets(&mut frequent);
+    frequent
+}
+
+/// Reduces frequent itemsets to the closed ones, which have no proper superset of equal support.
+///
+/// `itemsets` should be the complete output of a miner; order is preserved.
+pub fn closed(itemsets: &[Itemset]) -> Vec<Itemset> {
+    let (_, not_closed) = immediate_supersets(itemsets);
+    itemsets
+        .iter()
+        .filter(|s| !not_closed.contains(s.items.as_slice()))
+        .cloned()
+        .collect()
+}
+
+/// Reduces frequent itemsets to the maximal ones, which have no frequent proper superset.
+///
+/// `itemsets` should be the complete output of a miner; order is preserved.
+pub fn maximal(itemsets: &[Itemset]) -> Vec<Itemset> {
+    let (not_maximal, _) = immediate_supersets(itemsets);
+    itemsets
+        .iter()
+        .filter(|s| !not_maximal.contains(s.items.as_slice()))
+        .cloned()
+        .collect()
+}
+
+// Collects the itemsets that have a frequent superset one item larger, and those among them where
+// that superset has equal support. By downward closure, checking one item larger is enough.
+fn immediate_supersets(itemsets: &[Itemset]) -> (HashSet<Vec<String>>, HashSet<Vec<String>>) {
+    let supports: HashMap<&[String], f32> = itemsets.iter().map(|s| (s.items.as_slice(), s.support)).collect();
+    let mut with_superset = HashSet::new();
+    let mut with_equal_superset = HashSet::new();
+
+    for itemset in itemsets.iter().filter(|s| s.items.len() > 1) {
+        for skip in 0..itemset.items.len() {
+            let mut subset = itemset.items.clone();
+            subset.remove(skip);
+            if supports.get(subset.as_slice()) == Some(&itemset.support) {
+                with_equal_superset.insert(subset.clone());
+            }
+            with_superset.insert(subset);
         }
     }
-    return (max_lift, max_prod);
+    (with_superset, with_equal_superset)
+}
+
+/// Generates every rule `A -> C` where `A` and `C` partition a frequent itemset of two or more items.
+///
+/// `itemsets` should be the complete output of a miner, so that the support of every antecedent and
+/// consequent can be looked up. Rules below `min_confidence` or `min_lift` are dropped and the rest
+/// are sorted by `rank_by`, best first. Conviction is infinite for rules that always hold.
+pub fn rules(itemsets: &[Itemset], min_confidence: f32, min_lift: f32, rank_by: Metric) -> Vec<Rule> {
+    let supports: HashMap<&[String], f32> = itemsets.iter().map(|s| (s.items.as_slice(), s.support)).collect();
+    let mut rules = Vec::new();
+
+    for itemset in itemsets.iter().filter(|s| s.items.len() > 1) {
+        let n_items = itemset.items.len();
+        // Every non-empty proper subset of the itemset, encoded as a bitmask, is an antecedent
+        for mask in 1..(1u64 << n_items) - 1 {
+            let (antecedent, consequent): (Vec<_>, Vec<_>) = itemset.items.iter().enumerate().partition(|(i, _)| mask & (1 << i) != 0);
+            let antecedent: Vec<String> = antecedent.into_iter().map(|(_, p)| p.clone()).collect();
+            let consequent: Vec<String> = consequent.into_iter().map(|(_, p)| p.clone()).collect();
+
+            let (support_a, support_c) = match (supports.get(antecedent.as_slice()), supports.get(consequent.as_slice())) {
+                (Some(a), Some(c)) => (*a, *c),
+                _ => continue, // Subset missing from an incomplete itemset list
+            };
+
+            let rule = Rule::new(antecedent, consequent, itemset.support, support_a, support_c);
+            if rule.confidence >= min_confidence && rule.lift >= min_lift {
+                rules.push(rule);
+            }
+        }
+    }
+
+    rules.sort_by(|a, b| b.metric(rank_by).partial_cmp(&a.metric(rank_by)).unwrap());
+    rules
+}
+
+/// Mines the same itemsets as [`mine`] with the FP-Growth algorithm.
+///
+/// Transactions are compressed into a prefix tree of frequent items once, after which patterns are
+/// grown recursively from conditional trees instead of generating and counting candidates.
+pub fn fp_growth(transactions: &[HashSet<String>], min_support: f32) -> Vec<Itemset> {
+    let n_transactions = transactions.len();
+    let min_count = min_count(min_support, n_transactions);
+
+    let mut products: Vec<&String> = transactions.iter().flatten().collect::<HashSet<_>>().into_iter().collect();
+    products.sort();
+    let ids: HashMap<&String, usize> = products.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    let paths: Vec<(Vec<usize>, usize)> = transactions.iter().map(|t| (t.iter().map(|p| ids[p]).collect(), 1)).collect();
+    let mut patterns = Vec::new();
+    fp_grow(&paths, min_count, &mut Vec::new(), &mut patterns);
+
+    let mut frequent: Vec<Itemset> = patterns
+        .into_iter()
+        .map(|(pattern, count)| {
+            let mut items: Vec<String> = pattern.iter().map(|&i| products[i].clone()).collect();
+            items.sort();
+            Itemset {
+                items,
+                support: count as f32 / n_transactions as f32,
+            }
+        })
+        .collect();
+    sort_itemsets(&mut frequent);
+    frequent
+}
+
+/// Mines the same itemsets as [`mine`] with the Eclat algorithm.
+///
+/// Itemsets are extended depth-first by intersecting the transaction bitsets of the database, such
+/// as one returned by [`read_file`]. The equivalence classes of the frequent single products are
+/// distributed over `n_threads` threads, a value of 0 or 1 mining on the calling thread.
+pub fn eclat(db: &TransactionDb, min_support: f32, n_threads: usize) -> Vec<Itemset> {
+    let n_transactions = db.len();
+    let min_count = min_count(min_support, n_transactions);
+
+    let mut roots: Vec<(usize, Vec<u64>)> = (0..db.products().len())
+        .filter(|&id| db.count(&[id]) >= min_count)
+        .map(|id| (id, db.bitsets[id].clone()))
+        .collect();
+    roots.sort_by(|(a, _), (b, _)| db.products()[*a].cmp(&db.products()[*b]));
+
+    // Class i holds every itemset whose smallest product is roots[i]
+    let classes: Vec<Vec<(Vec<usize>, usize)>> = map_indices(roots.len(), n_threads, |i| {
+        let (id, bitset) = &roots[i];
+        let mut patterns = vec![(vec![*id], popcount(bitset))];
+        eclat_extend(&mut vec![*id], bitset, &roots[i + 1..], min_count, &mut patterns);
+        patterns
+    });
+
+    let mut frequent: Vec<Itemset> = classes
+        .into_iter()
+        .flatten()
+        .map(|(pattern, count)| {
+            let mut items: Vec<String> = pattern.iter().map(|&i| db.products()[i].clone()).collect();
+            items.sort();
+            Itemset {
+                items,
+                support: count as f32 / n_transactions as f32,
+            }
+        })
+        .collect();
+    sort_itemsets(&mut frequent);
+    frequent
+}
+
+// Extends `prefix` with every sibling whose intersected tidset stays frequent, then recurses into
+// the equivalence class of that extension.
+fn eclat_extend(
+    prefix: &mut Vec<usize>,
+    bitset: &[u64],
+    siblings: &[(usize, Vec<u64>)],
+    min_count: usize,
+    patterns: &mut Vec<(Vec<usize>, usize)>,
+) {
+    let extensions: Vec<(usize, Vec<u64>)> = siblings
+        .iter()
+        .map(|(id, other)| (*id, bitset.iter().zip(other).map(|(a, b)| a & b).collect::<Vec<u64>>()))
+        .filter(|(_, joint)| popcount(joint) >= min_count)
+        .collect();
+
+    for (i, (id, joint)) in extensions.iter().enumerate() {
+        prefix.push(*id);
+        patterns.push((prefix.clone(), popcount(joint)));
+        eclat_extend(prefix, joint, &extensions[i + 1..], min_count, patterns);
+        prefix.pop();
+    }
+}
+
+fn popcount(bitset: &[u64]) -> usize {
+    bitset.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+/// Mines all sequential patterns contained in at least `min_support` of the customers' sequences,
+/// using the PrefixSpan algorithm.
+///
+/// Each frequent prefix is grown either by adding a product to its last itemset or by appending a
+/// new itemset, counting candidates only in the suffixes of the sequences that contain the prefix.
+/// Patterns are returned ordered by their number of products, then lexicographically.
+pub fn prefix_span(sequences: &SequenceDb, min_support: f32) -> Vec<SequentialPattern> {
+    let n_sequences = sequences.len();
+    let min_count = min_count(min_support, n_sequences);
+
+    // The empty prefix ends before the first transaction of every sequence
+    let projected: Vec<(usize, Option<usize>)> = (0..n_sequences).map(|sid| (sid, None)).collect();
+    let mut patterns = Vec::new();
+    span(sequences, &mut Vec::new(), &projected, min_count, &mut patterns);
+
+    let mut frequent: Vec<SequentialPattern> = patterns
+        .into_iter()
+        .map(|(pattern, count)| {
+            let sequence = pattern
+                .iter()
+                .map(|itemset| {
+                    let mut items: Vec<String> = itemset.iter().map(|&id| sequences.products[id].clone()).collect();
+                    items.sort();
+                    items
+                })
+                .collect();
+            SequentialPattern {
+                sequence,
+                support: count as f32 / n_sequences as f32,
+            }
+        })
+        .collect();
+    frequent.sort_by(|a, b| {
+        let len = |p: &SequentialPattern| p.sequence.iter().map(|itemset| itemset.len()).sum::<usize>();
+        len(a).cmp(&len(b)).then_with(|| a.sequence.cmp(&b.sequence))
+    });
+    frequent
+}
+
+// Grows `prefix`, whose earliest match in each projected sequence ends at the given transaction.
+fn span(
+    db: &SequenceDb,
+    prefix: &mut Vec<Vec<usize>>,
+    projected: &[(usize, Option<usize>)],
+    min_count: usize,
+    patterns: &mut Vec<(Vec<Vec<usize>>, usize)>,
+) {
+    let last: Vec<usize> = prefix.last().cloned().unwrap_or_default();
+    let contains = |transaction: &[usize], itemset: &[usize]| itemset.iter().all(|id| transaction.binary_search(id).is_ok());
+
+    // Products counted once per sequence, either joining the last itemset or starting a new one
+    let mut itemset_counts: HashMap<usize, usize> = HashMap::new();
+    let mut sequence_counts: HashMap<usize, usize> = HashMap::new();
+    for (sid, end) in projected.iter() {
+        let sequence = &db.sequences[*sid];
+        let mut itemset_extensions: HashSet<usize> = HashSet::new();
+        let mut sequence_extensions: HashSet<usize> = HashSet::new();
+
+        if let Some(end) = end {
+            let max_last = last[last.len() - 1];
+            for transaction in sequence[*end..].iter().filter(|t| contains(t, &last)) {
+                itemset_extensions.extend(transaction.iter().filter(|&&id| id > max_last));
+            }
+        }
+        let next = end.map_or(0, |end| end + 1);
+        for transaction in sequence[next..].iter() {
+            sequence_extensions.extend(transaction.iter());
+        }
+
+        itemset_extensions
+            .into_iter()
+            .for_each(|id| *itemset_counts.entry(id).or_insert(0) += 1);
+        sequence_extensions
+            .into_iter()
+            .for_each(|id| *sequence_counts.entry(id).or_insert(0) += 1);
+    }
+
+    for (id, count) in itemset_counts.into_iter().filter(|(_, count)| *count >= min_count) {
+        let mut extended = last.clone();
+        extended.push(id);
+        let next_projected: Vec<(usize, Option<usize>)> = projected
+            .iter()
+            .filter_map(|(sid, end)| {
+                let sequence = &db.sequences[*sid];
+                (end.unwrap_or(0)..sequence.len())
+                    .find(|&t| contains(&sequence[t], &extended))
+                    .map(|t| (*sid, Some(t)))
+            })
+            .collect();
+
+        prefix.pop();
+        prefix.push(extended);
+        patterns.push((prefix.clone(), count));
+        span(db, prefix, &next_projected, min_count, patterns);
+        prefix.pop();
+        prefix.push(last.clone());
+    }
+
+    for (id, count) in sequence_counts.into_iter().filter(|(_, count)| *count >= min_count) {
+        let next_projected: Vec<(usize, Option<usize>)> = projected
+            .iter()
+            .filter_map(|(sid, end)| {
+                let sequence = &db.sequences[*sid];
+                (end.map_or(0, |end| end + 1)..sequence.len())
+                    .find(|&t| contains(&sequence[t], &[id]))
+                    .map(|t| (*sid, Some(t)))
+            })
+            .collect();
+
+        prefix.push(vec![id]);
+        patterns.push((prefix.clone(), count));
+        span(db, prefix, &next_projected, min_count, patterns);
+        prefix.pop();
+    }
+}
+
+struct FpNode {
+    item: usize,
+    count: usize,
+    parent: usize,
+    children: HashMap<usize, usize>,
+}
+
+// Builds an FP-tree from weighted paths and emits every frequent pattern ending in `suffix`,
+// recursing into the conditional pattern base of each frequent item.
+fn fp_grow(paths: &[(Vec<usize>, usize)], min_count: usize, suffix: &mut Vec<usize>, patterns: &mut Vec<(Vec<usize>, usize)>) {
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for (path, count) in paths.iter() {
+        for item in path.iter() {
+            *counts.entry(*item).or_insert(0) += count;
+        }
+    }
+    counts.retain(|_, count| *count >= min_count);
+    if counts.is_empty() {
+        return;
+    }
+
+    // Node 0 is the root; items are inserted in descending frequency so common prefixes are shared
+    let mut nodes = vec![FpNode {
+        item: usize::MAX,
+        count: 0,
+        parent: 0,
+        children: HashMap::new(),
+    }];
+    let mut header: HashMap<usize, Vec<usize>> = HashMap::new();
+
+    for (path, count) in paths.iter() {
+        let mut path: Vec<usize> = path.iter().copied().filter(|item| counts.contains_key(item)).collect();
+        path.sort_by(|a, b| counts[b].cmp(&counts[a]).then(a.cmp(b)));
+
+        let mut current = 0;
+        for item in path {
+            current = match nodes[current].children.get(&item) {
+                Some(&child) => child,
+                None => {
+                    let child = nodes.len();
+                    nodes.push(FpNode {
+                        item,
+                        count: 0,
+                        parent: current,
+                        children: HashMap::new(),
+                    });
+                    nodes[current].children.insert(item, child);
+                    header.entry(item).or_default().push(child);
+                    child
+                }
+            };
+            nodes[current].count += count;
+        }
+    }
+
+    for (item, count) in counts.iter() {
+        suffix.push(*item);
+        patterns.push((suffix.clone(), *count));
+
+        let conditional_paths: Vec<(Vec<usize>, usize)> = header[item]
+            .iter()
+            .map(|&node| {
+                let mut path = Vec::new();
+                let mut current = nodes[node].parent;
+                while current != 0 {
+                    path.push(nodes[current].item);
+                    current = nodes[current].parent;
+                }
+                (path, nodes[node].count)
+            })
+            .collect();
+        fp_grow(&conditional_paths, min_count, suffix, patterns);
+        suffix.pop();
+    }
+}
+
+// Joins sorted k-itemsets that share their first k - 1 items, keeping only candidates whose
+// k-subsets are all frequent.
+fn generate_candidates(level: &[&[String]]) -> Vec<Vec<String>> {
+    let frequent: HashSet<&[String]> = level.iter().copied().collect();
+    let mut candidates = Vec::new();
+
+    for (i, a) in level.iter().enumerate() {
+        let k = a.len();
+        for b in level[i + 1..].iter() {
+            if a[..k - 1] != b[..k - 1] {
+                break; // Level is sorted, so no later itemset shares this prefix
+            }
+            let mut candidate = a.to_vec();
+            candidate.push(b[k - 1].clone());
+
+            // Subsets dropping either of the last two items are `a` and `b` themselves
+            let all_subsets_frequent = (0..k - 1).all(|skip| {
+                let subset: Vec<String> = candidate
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != skip)
+                    .map(|(_, p)| p.clone())
+                    .collect();
+                frequent.contains(subset.as_slice())
+            });
+            if all_subsets_frequent {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates
+}
+
+// Smallest absolute count that reaches `min_support`, never less than one occurrence.
+fn min_count(min_support: f32, n_transactions: usize) -> usize {
+    ((min_support * n_transactions as f32).ceil() as usize).max(1)
+}
+
+fn sort_itemsets(itemsets: &mut [Itemset]) {
+    itemsets.sort_by(|a, b| a.items.len().cmp(&b.items.len()).then_with(|| a.items.cmp(&b.items)));
 }
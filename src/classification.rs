@@ -1,34 +1,279 @@
-extern crate itertools;
-use itertools::Itertools;
+use crate::distance::{Distance, Euclidean};
+use crate::spatial::{Algorithm, NeighbourIndex, SpatialIndex};
+use crate::validation::{check_fit, check_rows, encode_labels};
+use std::hash::Hash;
 
+/// Labels `x` by a majority vote among its `k` nearest rows of `y`, whose last column holds the label.
 pub fn single_knn(k: usize, x: &[f32; 2], y: &[[f32; 3]]) -> Option<f32> {
-    let mut distances: Vec<(f32, f32)> = y
-        .iter()
-        .map(|point: &[f32; 3]| (point[2], (x[0] - point[0]).hypot(x[1] - point[1])))
-        .collect();
-    distances.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+    single_knn_with_distance(k, x, y, &Euclidean)
+}
+
+/// Labels `x` like [`single_knn`], with neighbours found by `distance`.
+pub fn single_knn_with_distance<D: Distance + Clone>(k: usize, x: &[f32; 2], y: &[[f32; 3]], distance: &D) -> Option<f32> {
+    point_classifier(k, y, distance).predict(&[x.to_vec()])[0].map(f32::from_bits)
+}
+
+pub fn k_nearest_neighbours(k: usize, x: &[[f32; 2]], y: &[[f32; 3]]) -> Vec<Option<f32>> {
+    k_nearest_neighbours_with_distance(k, x, y, &Euclidean)
+}
+
+pub fn k_nearest_neighbours_with_distance<D: Distance + Clone>(k: usize, x: &[[f32; 2]], y: &[[f32; 3]], distance: &D) -> Vec<Option<f32>> {
+    let queries: Vec<Vec<f32>> = x.iter().map(|point| point.to_vec()).collect();
+    point_classifier(k, y, distance)
+        .predict(&queries)
+        .into_iter()
+        .map(|label| label.map(f32::from_bits))
+        .collect()
+}
+
+// Float labels are voted on by their bit pattern, as f32 is not hashable.
+fn point_classifier<D: Distance + Clone>(k: usize, y: &[[f32; 3]], distance: &D) -> KNeighborsClassifier<u32, D> {
+    let features: Vec<Vec<f32>> = y.iter().map(|point| point[..2].to_vec()).collect();
+    let labels: Vec<u32> = y.iter().map(|point| point[2].to_bits()).collect();
+    let mut knn = KNeighborsClassifier::with_distance(k, distance.clone());
+    knn.fit(&features, &labels);
+    knn
+}
 
-    let k_distances = distances[0..k].into_iter().map(|tuple| tuple.0 as u32);
-    let unique_dist = k_distances.clone().unique();
+fn check_k(k: usize) {
+    if k == 0 {
+        panic!("k must be at least 1, got 0");
+    }
+}
 
-    let unique_frequency = unique_dist.map(|unique_dist: u32| {
-        (
-            unique_dist,
-            k_distances
-                .clone()
-                .filter(|dist: &u32| *dist == unique_dist)
-                .count(),
-        )
-    });
+/// How much each neighbour's vote counts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weighting {
+    Uniform,
+    /// Votes weighted by inverse distance; neighbours at distance zero outvote all others.
+    Distance,
+}
 
-    match unique_frequency.max_by_key(|(unique_dist, _)| *unique_dist) {
-        Some(x) => return Some(x.0 as f32),
-        None => return None,
+impl Weighting {
+    fn weights(&self, neighbours: &[(usize, f32)]) -> Vec<f32> {
+        let exact = neighbours.iter().any(|(_, distance)| *distance == 0.);
+        neighbours
+            .iter()
+            .map(|(_, distance)| match self {
+                Weighting::Uniform => 1.,
+                Weighting::Distance if exact => (*distance == 0.) as u8 as f32,
+                Weighting::Distance => 1. / distance,
+            })
+            .collect()
     }
 }
 
-pub fn k_nearest_neighbours(k: usize, x: &[[f32; 2]], y: &[[f32; 3]]) -> Vec<Option<f32>> {
-    x.iter()
-        .map(|point: &[f32; 2]| single_knn(k, point, y))
-        .collect()
+/// How a vote tied between several labels is decided.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TieBreak {
+    /// The tied label of the nearest neighbour wins.
+    Nearest,
+    /// The tied label seen first during `fit` wins.
+    FirstClass,
+    /// The farthest neighbour is dropped until the tie is broken.
+    ReduceK,
+}
+
+/// k-nearest-neighbours classifier over any number of features and any hashable label type.
+///
+/// Queries are labelled by a vote among the `k` closest training points, Euclidean unless another
+/// distance is given. Neighbours are found by brute force unless `algorithm` selects a spatial
+/// index; `distance` and `algorithm` take effect on the next `fit`.
+pub struct KNeighborsClassifier<L, D = Euclidean> {
+    pub k: usize,
+    pub weighting: Weighting,
+    pub tie_break: TieBreak,
+    pub distance: D,
+    pub algorithm: Algorithm,
+    neighbours: Neighbours<D>,
+    classes: Vec<L>,
+    class_ids: Vec<usize>,
+}
+
+impl<L: Eq + Hash + Clone> KNeighborsClassifier<L> {
+    pub fn new(k: usize) -> KNeighborsClassifier<L> {
+        KNeighborsClassifier::with_distance(k, Euclidean)
+    }
+}
+
+impl<L: Eq + Hash + Clone, D: Distance + Clone> KNeighborsClassifier<L, D> {
+    pub fn with_distance(k: usize, distance: D) -> KNeighborsClassifier<L, D> {
+        check_k(k);
+        KNeighborsClassifier {
+            k,
+            weighting: Weighting::Uniform,
+            tie_break: TieBreak::Nearest,
+            distance,
+            algorithm: Algorithm::BruteForce,
+            neighbours: Neighbours::default(),
+            classes: Vec::new(),
+            class_ids: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], labels: &[L]) {
+        check_k(self.k);
+        check_fit(features, labels.len());
+        self.neighbours = Neighbours::fit(features, self.algorithm, self.distance.clone());
+        let (classes, class_ids) = encode_labels(labels);
+        self.classes = classes;
+        self.class_ids = class_ids;
+    }
+
+    /// Distinct training labels in order of first appearance, the columns of `predict_proba`.
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// Predicts a label per query, `None` when the classifier holds no training points.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<L>> {
+        check_k(self.k);
+        queries.iter().map(|query| self.predict_one(query)).collect()
+    }
+
+    /// Share of the (weighted) vote each class receives per query, ordered as `classes`.
+    pub fn predict_proba(&self, queries: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        check_k(self.k);
+        queries
+            .iter()
+            .map(|query| {
+                let votes = self.votes(&self.neighbours(query));
+                let total: f32 = votes.iter().sum();
+                votes.iter().map(|v| v / total).collect()
+            })
+            .collect()
+    }
+
+    fn predict_one(&self, query: &[f32]) -> Option<L> {
+        let mut neighbours = self.neighbours(query);
+        loop {
+            let votes = self.votes(&neighbours);
+            let best = votes.iter().cloned().fold(0., f32::max);
+            let tied: Vec<usize> = (0..votes.len()).filter(|&c| votes[c] > 0. && votes[c] == best).collect();
+
+            let winner = match (tied.len(), self.tie_break) {
+                (0, _) => None,
+                (1, _) => Some(tied[0]),
+                (_, TieBreak::Nearest) => neighbours.iter().map(|(i, _)| self.class_ids[*i]).find(|c| tied.contains(c)),
+                (_, TieBreak::FirstClass) => tied.first().copied(),
+                (_, TieBreak::ReduceK) => {
+                    neighbours.pop();
+                    continue;
+                }
+            };
+            return winner.map(|c| self.classes[c].clone());
+        }
+    }
+
+    // Summed vote per class of the given neighbours.
+    fn votes(&self, neighbours: &[(usize, f32)]) -> Vec<f32> {
+        let mut votes = vec![0.; self.classes.len()];
+        for ((i, _), weight) in neighbours.iter().zip(self.weighting.weights(neighbours)) {
+            votes[self.class_ids[*i]] += weight;
+        }
+        votes
+    }
+
+    fn neighbours(&self, query: &[f32]) -> Vec<(usize, f32)> {
+        self.neighbours.k_nearest(query, self.k)
+    }
+}
+
+/// k-nearest-neighbours regressor, predicting the mean target of the `k` closest training points,
+/// weighted by inverse distance when `weighting` says so.
+///
+/// Configured like [`KNeighborsClassifier`]; `distance` and `algorithm` take effect on the next `fit`.
+pub struct KNeighborsRegressor<D = Euclidean> {
+    pub k: usize,
+    pub weighting: Weighting,
+    pub distance: D,
+    pub algorithm: Algorithm,
+    neighbours: Neighbours<D>,
+    targets: Vec<f32>,
+}
+
+impl KNeighborsRegressor {
+    pub fn new(k: usize) -> KNeighborsRegressor {
+        KNeighborsRegressor::with_distance(k, Euclidean)
+    }
+}
+
+impl<D: Distance + Clone> KNeighborsRegressor<D> {
+    pub fn with_distance(k: usize, distance: D) -> KNeighborsRegressor<D> {
+        check_k(k);
+        KNeighborsRegressor {
+            k,
+            weighting: Weighting::Uniform,
+            distance,
+            algorithm: Algorithm::BruteForce,
+            neighbours: Neighbours::default(),
+            targets: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], targets: &[f32]) {
+        check_k(self.k);
+        check_fit(features, targets.len());
+        self.neighbours = Neighbours::fit(features, self.algorithm, self.distance.clone());
+        self.targets = targets.to_vec();
+    }
+
+    /// Predicts a value per query, `None` when the regressor holds no training points.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<f32>> {
+        check_k(self.k);
+        queries
+            .iter()
+            .map(|query| {
+                let neighbours = self.neighbours.k_nearest(query, self.k);
+                if neighbours.is_empty() {
+                    return None;
+                }
+                let weights = self.weighting.weights(&neighbours);
+                let weighted_sum: f32 = neighbours.iter().zip(weights.iter()).map(|((i, _), w)| self.targets[*i] * w).sum();
+                Some(weighted_sum / weights.iter().sum::<f32>())
+            })
+            .collect()
+    }
+}
+
+// Training points indexed for neighbour search, shared by the neighbour-based estimators.
+struct Neighbours<D> {
+    index: Option<NeighbourIndex<D>>,
+    n_features: usize,
+}
+
+impl<D> Default for Neighbours<D> {
+    fn default() -> Neighbours<D> {
+        Neighbours {
+            index: None,
+            n_features: 0,
+        }
+    }
+}
+
+impl<D: Distance> Neighbours<D> {
+    fn fit(features: &[Vec<f32>], algorithm: Algorithm, distance: D) -> Neighbours<D> {
+        check_rows(features);
+        Neighbours {
+            index: Some(NeighbourIndex::build(algorithm, features, distance)),
+            n_features: features.first().map_or(0, |row| row.len()),
+        }
+    }
+
+    // Indices and distances of the k training points closest to `query`, nearest first.
+    fn k_nearest(&self, query: &[f32], k: usize) -> Vec<(usize, f32)> {
+        match &self.index {
+            Some(index) => {
+                if self.n_features != query.len() {
+                    panic!(
+                        "Query and training rows have different numbers of features, got {} and {}",
+                        query.len(),
+                        self.n_features
+                    );
+                }
+                index.k_nearest(query, k)
+            }
+            None => Vec::new(),
+        }
+    }
 }
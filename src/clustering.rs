@@ -8,74 +8,791 @@
 //            - new centroid = mean of all points assigned to that cluster
 // 6. End
 // use ndarray::{array, Array, Dim, Axis};
-use rand::Rng;
+use crate::distance::{Distance, Euclidean};
+use crate::parallel::map_indices;
+use crate::spatial::{Algorithm, NeighbourIndex, SpatialIndex};
+use crate::validation::check_rows;
+use rand::prelude::*;
 
-pub struct KMeans {
-    n_clusters: u8,
+/// Clustering models that assign rows to the closest of their cluster centres, so that fitted
+/// models can be used interchangeably.
+pub trait CentroidModel {
+    /// Fitted cluster centres, the cluster ids `predict` returns being their positions.
+    fn cluster_centers(&self) -> &[Vec<f32>];
+
+    /// Distance rows are assigned to centres by.
+    fn distance(&self) -> &dyn Distance;
+
+    /// Assigns every row to its closest cluster centre.
+    fn predict(&self, features: &[Vec<f32>]) -> Vec<usize> {
+        if self.cluster_centers().is_empty() {
+            panic!("Cluster centres have not been fitted");
+        }
+        features
+            .iter()
+            .map(|row| nearest_center(self.cluster_centers(), self.distance(), row).0)
+            .collect()
+    }
+}
+
+/// k-means clustering by Lloyd's algorithm, seeded with k-means++.
+///
+/// Iterates until no centre moves further than `tol` or `max_iter` iterations have passed. A
+/// cluster left empty is moved to the row farthest from its own centre. `n_init` independent
+/// initialisations are run over `n_threads` threads, a value of 0 or 1 running on the calling
+/// thread, and the run with the lowest inertia is kept. Run `i` draws from a generator seeded with
+/// `seed + i`, so fits are reproducible regardless of the number of threads.
+pub struct KMeans<D = Euclidean> {
+    pub n_clusters: usize,
+    pub max_iter: usize,
+    pub tol: f32,
+    pub n_init: usize,
+    pub n_threads: usize,
+    pub seed: u64,
+    pub distance: D,
     cluster_centers: Vec<Vec<f32>>,
+    inertia: f32,
+    n_iter: usize,
 }
 
 impl KMeans {
-    pub fn new(n_clusters: u8) -> KMeans {
+    pub fn new(n_clusters: usize) -> KMeans {
+        KMeans::with_distance(n_clusters, Euclidean)
+    }
+}
+
+impl<D: Distance> KMeans<D> {
+    pub fn with_distance(n_clusters: usize, distance: D) -> KMeans<D> {
         KMeans {
-            n_clusters: n_clusters,
+            n_clusters,
+            max_iter: 300,
+            tol: 1e-4,
+            n_init: 1,
+            n_threads: 1,
+            seed: 0,
+            distance,
             cluster_centers: vec![],
+            inertia: 0.,
+            n_iter: 0,
         }
     }
 
-    fn random_cluster_centers(&self, n_clusters: u8, n_features: &usize) -> Vec<Vec<f32>> {
-        let mut rng = rand::thread_rng();
+    pub fn fit(&mut self, features: &[Vec<f32>])
+    where
+        D: Sync,
+    {
+        check_clusters(features, self.n_clusters);
+        let runs = map_indices(self.n_init.max(1), self.n_threads, |r| {
+            self.run(features, &mut StdRng::seed_from_u64(self.seed.wrapping_add(r as u64)))
+        });
+        // The first of equally good runs is kept
+        let run = runs.into_iter().min_by(|a, b| a.inertia.total_cmp(&b.inertia)).unwrap();
+        self.cluster_centers = run.centers;
+        self.inertia = run.inertia;
+        self.n_iter = run.n_iter;
+    }
+
+    // One initialisation followed by Lloyd iterations.
+    fn run(&self, features: &[Vec<f32>], rng: &mut StdRng) -> Run {
+        let mut centers = kmeans_plus_plus(features, self.n_clusters, &self.distance, rng);
+        let mut n_iter = 0;
+        while n_iter < self.max_iter {
+            n_iter += 1;
+            let mut nearest: Vec<(usize, f32)> = features.iter().map(|row| nearest_center(&centers, &self.distance, row)).collect();
 
-        (0..n_clusters)
-            .map(|_: u8| {
-                (0..*n_features)
-                    .map(|_: usize| rng.gen::<f32>())
-                    .collect::<Vec<f32>>()
-            })
-            .collect::<Vec<Vec<f32>>>()
+            let n_features = features[0].len();
+            let mut sums = vec![vec![0.; n_features]; self.n_clusters];
+            let mut counts = vec![0; self.n_clusters];
+            for (row, (cluster, _)) in features.iter().zip(nearest.iter()) {
+                sums[*cluster].iter_mut().zip(row).for_each(|(sum, x)| *sum += x);
+                counts[*cluster] += 1;
+            }
+
+            // An empty cluster takes over the row farthest from its centre
+            for cluster in 0..self.n_clusters {
+                if counts[cluster] > 0 {
+                    continue;
+                }
+                let farthest = (0..features.len())
+                    .filter(|&i| counts[nearest[i].0] > 1)
+                    .max_by(|&a, &b| nearest[a].1.total_cmp(&nearest[b].1))
+                    .unwrap();
+                let (old, _) = nearest[farthest];
+                sums[old].iter_mut().zip(&features[farthest]).for_each(|(sum, x)| *sum -= x);
+                counts[old] -= 1;
+                sums[cluster] = features[farthest].clone();
+                counts[cluster] = 1;
+                nearest[farthest] = (cluster, 0.);
+            }
+
+            let new_centers: Vec<Vec<f32>> = sums
+                .iter()
+                .zip(counts.iter())
+                .map(|(sum, &count)| sum.iter().map(|s| s / count as f32).collect())
+                .collect();
+            let shift = centers
+                .iter()
+                .zip(new_centers.iter())
+                .map(|(old, new)| self.distance.distance(old, new))
+                .fold(0., f32::max);
+            centers = new_centers;
+            if shift <= self.tol {
+                break;
+            }
+        }
+
+        let inertia = features
+            .iter()
+            .map(|row| nearest_center(&centers, &self.distance, row).1.powi(2))
+            .sum();
+        Run { centers, inertia, n_iter }
+    }
+
+    /// See [`CentroidModel::cluster_centers`].
+    pub fn cluster_centers(&self) -> &[Vec<f32>] {
+        &self.cluster_centers
+    }
+
+    /// Sum over the training rows of the squared `distance` to their closest centre. That is the
+    /// squared Euclidean distance by default; a distance that is already squared, such as
+    /// [`SquaredEuclidean`](crate::distance::SquaredEuclidean), is squared once more.
+    pub fn inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    /// Number of Lloyd iterations the fit took.
+    pub fn n_iter(&self) -> usize {
+        self.n_iter
+    }
+
+    /// See [`CentroidModel::predict`].
+    pub fn predict(&self, features: &[Vec<f32>]) -> Vec<usize> {
+        CentroidModel::predict(self, features)
+    }
+}
+
+impl<D: Distance> CentroidModel for KMeans<D> {
+    fn cluster_centers(&self) -> &[Vec<f32>] {
+        KMeans::cluster_centers(self)
+    }
+
+    fn distance(&self) -> &dyn Distance {
+        &self.distance
+    }
+}
+
+/// k-means clustering on mini-batches, for data that arrives as a stream or does not fit in memory.
+///
+/// Centres are seeded with k-means++ on the first batch. Every row then moves its closest centre
+/// towards it by a learning rate of one over the number of rows that centre has absorbed, so
+/// centres settle as they see more data. `fit` draws `max_iter` batches of `batch_size` rows from
+/// data in memory, `partial_fit` takes batches as they come. Sampling and seeding draw from
+/// generators seeded with `seed`.
+pub struct MiniBatchKMeans<D = Euclidean> {
+    pub n_clusters: usize,
+    pub batch_size: usize,
+    pub max_iter: usize,
+    pub seed: u64,
+    pub distance: D,
+    cluster_centers: Vec<Vec<f32>>,
+    counts: Vec<usize>,
+}
+
+impl MiniBatchKMeans {
+    pub fn new(n_clusters: usize) -> MiniBatchKMeans {
+        MiniBatchKMeans::with_distance(n_clusters, Euclidean)
+    }
+}
+
+impl<D: Distance> MiniBatchKMeans<D> {
+    pub fn with_distance(n_clusters: usize, distance: D) -> MiniBatchKMeans<D> {
+        MiniBatchKMeans {
+            n_clusters,
+            batch_size: 100,
+            max_iter: 100,
+            seed: 0,
+            distance,
+            cluster_centers: Vec::new(),
+            counts: Vec::new(),
+        }
     }
-    fn distance_to_centroid(input: &Vec<f32>, cluster: &Vec<f32>) -> f32 {
-        input.iter().zip(cluster).map(|(a, b)| (b - a).powf(2.)).sum::<f32>().sqrt()
+
+    /// Fits from scratch on batches drawn with replacement from `features`.
+    pub fn fit(&mut self, features: &[Vec<f32>]) {
+        check_clusters(features, self.n_clusters);
+        self.cluster_centers.clear();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        for _ in 0..self.max_iter {
+            let batch: Vec<Vec<f32>> = (0..self.batch_size.max(self.n_clusters))
+                .map(|_| features[rng.gen_range(0..features.len())].clone())
+                .collect();
+            self.partial_fit(&batch);
+        }
     }
 
-    fn best_centroid(&self, input:  &Vec<f32>) -> usize {
-        let mut best_i: usize = 0;
-        let mut best_dist: f32 = 0.;
+    /// Updates the centres with one batch, the first batch needing at least `n_clusters` rows.
+    pub fn partial_fit(&mut self, batch: &[Vec<f32>]) {
+        if self.cluster_centers.is_empty() {
+            check_clusters(batch, self.n_clusters);
+            let mut rng = StdRng::seed_from_u64(self.seed);
+            self.cluster_centers = kmeans_plus_plus(batch, self.n_clusters, &self.distance, &mut rng);
+            self.counts = vec![0; self.n_clusters];
+        }
+        if let Some(row) = batch.iter().find(|row| row.len() != self.cluster_centers[0].len()) {
+            panic!(
+                "Rows and centres have different numbers of features, got {} and {}",
+                row.len(),
+                self.cluster_centers[0].len()
+            );
+        }
 
-        for i_val in self.cluster_centers.iter().enumerate() {
-            let (i, cluster) = i_val;
-            let dist = KMeans::distance_to_centroid(&input, cluster);
-            if dist > best_dist {
-                best_dist = dist;
-                best_i = i;
+        // Rows are assigned before any centre moves
+        let nearest: Vec<usize> = batch
+            .iter()
+            .map(|row| nearest_center(&self.cluster_centers, &self.distance, row).0)
+            .collect();
+        for (row, cluster) in batch.iter().zip(nearest) {
+            self.counts[cluster] += 1;
+            let learning_rate = 1. / self.counts[cluster] as f32;
+            for (center, x) in self.cluster_centers[cluster].iter_mut().zip(row) {
+                *center += learning_rate * (x - *center);
             }
         }
-        best_i
     }
 
-    pub fn fit(&mut self, x: &Vec<Vec<f32>>, epochs: u128) {
-        let n_features = x[0].len();
-        self.cluster_centers = self.random_cluster_centers(self.n_clusters, &n_features);
+    /// See [`CentroidModel::cluster_centers`].
+    pub fn cluster_centers(&self) -> &[Vec<f32>] {
+        &self.cluster_centers
+    }
+
+    /// See [`CentroidModel::predict`].
+    pub fn predict(&self, features: &[Vec<f32>]) -> Vec<usize> {
+        CentroidModel::predict(self, features)
+    }
+}
+
+impl<D: Distance> CentroidModel for MiniBatchKMeans<D> {
+    fn cluster_centers(&self) -> &[Vec<f32>] {
+        MiniBatchKMeans::cluster_centers(self)
+    }
+
+    fn distance(&self) -> &dyn Distance {
+        &self.distance
+    }
+}
+
+/// Density-based clustering: clusters are regions where rows have at least `min_samples` rows,
+/// themselves included, within `eps`, and rows in no such region are noise.
+///
+/// Neighbourhoods are found by brute force unless `algorithm` selects a spatial index. Clusters are
+/// numbered in order of their first row.
+pub struct DBSCAN<D = Euclidean> {
+    pub eps: f32,
+    pub min_samples: usize,
+    pub distance: D,
+    pub algorithm: Algorithm,
+    labels: Vec<Option<usize>>,
+    core_samples: Vec<usize>,
+}
 
-        for _ in 0..epochs {
-            let best_clusters = x.iter().map(|x_i| self.best_centroid(&x_i));
-            
-            let mut clusters: Vec<Vec<Vec<f32>>> = (0..self.n_clusters).map(|_| vec![vec![]]).collect();
+impl DBSCAN {
+    pub fn new(eps: f32, min_samples: usize) -> DBSCAN {
+        DBSCAN::with_distance(eps, min_samples, Euclidean)
+    }
+}
 
-            for row in best_clusters.zip(x) {
-                let (cluster, x_i) = row;
-                clusters[cluster].push(x_i.to_vec())
+impl<D: Distance + Clone> DBSCAN<D> {
+    pub fn with_distance(eps: f32, min_samples: usize, distance: D) -> DBSCAN<D> {
+        DBSCAN {
+            eps,
+            min_samples,
+            distance,
+            algorithm: Algorithm::BruteForce,
+            labels: Vec::new(),
+            core_samples: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>]) {
+        check_rows(features);
+        let index = NeighbourIndex::build(self.algorithm, features, self.distance.clone());
+        let neighbourhoods: Vec<Vec<usize>> = features
+            .iter()
+            .map(|row| index.within_radius(row, self.eps).into_iter().map(|(i, _)| i).collect())
+            .collect();
+        let is_core: Vec<bool> = neighbourhoods.iter().map(|n| n.len() >= self.min_samples).collect();
+
+        // Clusters grow from core rows, claiming the border rows they reach first
+        let mut labels = vec![None; features.len()];
+        let mut n_clusters = 0;
+        for start in (0..features.len()).filter(|&i| is_core[i]) {
+            if labels[start].is_some() {
+                continue;
             }
-            
-            for i_val in clusters.iter().enumerate() {
-                let (i, cluster) = i_val;
-                let transpose: Vec<Vec<f32>> = (0..cluster[0].len()).map(|i| cluster.iter().map(|inner| inner[i].clone()).collect::<Vec<f32>>()).collect();
-                self.cluster_centers[i] = transpose.iter().map(|coord| coord.iter().sum::<f32>() / n_features as f32).collect();
+            labels[start] = Some(n_clusters);
+            let mut frontier = vec![start];
+            while let Some(core) = frontier.pop() {
+                for &neighbour in &neighbourhoods[core] {
+                    if labels[neighbour].is_none() {
+                        labels[neighbour] = Some(n_clusters);
+                        if is_core[neighbour] {
+                            frontier.push(neighbour);
+                        }
+                    }
+                }
             }
+            n_clusters += 1;
         }
+
+        self.labels = renumber(&labels);
+        self.core_samples = (0..features.len()).filter(|&i| is_core[i]).collect();
     }
 
-    pub fn predict(&self, x: Vec<Vec<f32>>) -> Vec<usize> {
-        x.iter().map(|x_i| self.best_centroid(&x_i)).collect()
+    /// Cluster of every training row, `None` for noise.
+    pub fn labels(&self) -> &[Option<usize>] {
+        &self.labels
     }
+
+    /// Indices of the rows with a dense enough neighbourhood to grow a cluster.
+    pub fn core_samples(&self) -> &[usize] {
+        &self.core_samples
+    }
+}
+
+/// Hierarchical DBSCAN, finding clusters of varying density without a fixed `eps`.
+///
+/// Distances are replaced by mutual reachability distances, at least the distance of either row
+/// to its `min_samples`-th nearest neighbour, itself included, defaulting to `min_cluster_size`.
+/// The single-linkage tree of those distances is condensed into clusters of at least
+/// `min_cluster_size` rows, and the most stable clusters are kept; rows in none of them are noise.
+/// Nearest neighbours are found by brute force unless `algorithm` selects a spatial index.
+pub struct HDBSCAN<D = Euclidean> {
+    pub min_cluster_size: usize,
+    pub min_samples: Option<usize>,
+    pub distance: D,
+    pub algorithm: Algorithm,
+    labels: Vec<Option<usize>>,
+}
+
+impl HDBSCAN {
+    pub fn new(min_cluster_size: usize) -> HDBSCAN {
+        HDBSCAN::with_distance(min_cluster_size, Euclidean)
+    }
+}
+
+impl<D: Distance + Clone> HDBSCAN<D> {
+    pub fn with_distance(min_cluster_size: usize, distance: D) -> HDBSCAN<D> {
+        HDBSCAN {
+            min_cluster_size,
+            min_samples: None,
+            distance,
+            algorithm: Algorithm::BruteForce,
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>]) {
+        check_rows(features);
+        let n = features.len();
+        if n < 2 {
+            self.labels = vec![None; n];
+            return;
+        }
+        let min_cluster_size = self.min_cluster_size.max(2);
+        let min_samples = self.min_samples.unwrap_or(min_cluster_size).max(1);
+        let index = NeighbourIndex::build(self.algorithm, features, self.distance.clone());
+        let core_distances: Vec<f32> = features
+            .iter()
+            .map(|row| index.k_nearest(row, min_samples).last().map_or(0., |(_, d)| *d))
+            .collect();
+        let reachability = |a: usize, b: usize| {
+            self.distance
+                .distance(&features[a], &features[b])
+                .max(core_distances[a])
+                .max(core_distances[b])
+        };
+
+        // Prim's minimum spanning tree of the mutual reachability graph
+        let mut in_tree = vec![false; n];
+        let mut closest: Vec<(f32, usize)> = vec![(f32::INFINITY, 0); n];
+        let mut edges: Vec<(usize, usize, f32)> = Vec::with_capacity(n - 1);
+        let mut current = 0;
+        for _ in 1..n {
+            in_tree[current] = true;
+            for other in (0..n).filter(|&o| !in_tree[o]) {
+                let d = reachability(current, other);
+                if d < closest[other].0 {
+                    closest[other] = (d, current);
+                }
+            }
+            let next = (0..n)
+                .filter(|&o| !in_tree[o])
+                .min_by(|&a, &b| closest[a].0.total_cmp(&closest[b].0))
+                .unwrap();
+            edges.push((closest[next].1, next, closest[next].0));
+            current = next;
+        }
+        edges.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+        let tree = Dendrogram::from_edges(n, &edges);
+        let condensed = Condensed::new(&tree, min_cluster_size);
+        self.labels = renumber(&condensed.select());
+    }
+
+    /// Cluster of every training row, `None` for noise.
+    pub fn labels(&self) -> &[Option<usize>] {
+        &self.labels
+    }
+}
+
+/// How the distance between two clusters follows from the distances between their rows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Linkage {
+    /// Distance of the closest pair of rows.
+    Single,
+    /// Distance of the farthest pair of rows.
+    Complete,
+    /// Mean distance over all pairs of rows.
+    Average,
+    /// Increase in within-cluster variance when merging, meaningful for Euclidean distances only.
+    Ward,
+}
+
+/// Agglomerative hierarchical clustering, merging the two closest clusters until one remains.
+///
+/// The full merge tree is kept, so it can be inspected as a dendrogram and cut into any number of
+/// clusters or at any distance without refitting.
+pub struct AgglomerativeClustering<D = Euclidean> {
+    pub linkage: Linkage,
+    pub distance: D,
+    dendrogram: Dendrogram,
+}
+
+impl AgglomerativeClustering {
+    pub fn new(linkage: Linkage) -> AgglomerativeClustering {
+        AgglomerativeClustering::with_distance(linkage, Euclidean)
+    }
+}
+
+impl<D: Distance> AgglomerativeClustering<D> {
+    pub fn with_distance(linkage: Linkage, distance: D) -> AgglomerativeClustering<D> {
+        AgglomerativeClustering {
+            linkage,
+            distance,
+            dendrogram: Dendrogram::from_edges(0, &[]),
+        }
+    }
+
+    /// Builds the merge tree by following chains of nearest neighbours until two clusters are each
+    /// other's nearest, which makes the same merges as always joining the closest pair.
+    pub fn fit(&mut self, features: &[Vec<f32>]) {
+        check_rows(features);
+        let n = features.len();
+        let mut distances: Vec<Vec<f32>> = features
+            .iter()
+            .map(|a| features.iter().map(|b| self.distance.distance(a, b)).collect())
+            .collect();
+        let mut sizes = vec![1; n];
+        let mut active = vec![true; n];
+        // Merges of the clusters represented by their first row, not yet in order of distance
+        let mut merges: Vec<(usize, usize, f32)> = Vec::with_capacity(n.saturating_sub(1));
+        let mut chain: Vec<usize> = Vec::new();
+
+        while merges.len() + 1 < n {
+            if chain.is_empty() {
+                chain.push(active.iter().position(|a| *a).unwrap());
+            }
+            let (a, b) = loop {
+                let a = chain[chain.len() - 1];
+                let previous = if chain.len() > 1 { Some(chain[chain.len() - 2]) } else { None };
+                // The previous cluster wins ties, so the chain always ends
+                let mut nearest = previous.map_or((usize::MAX, f32::INFINITY), |p| (p, distances[a][p]));
+                for other in (0..n).filter(|&o| active[o] && o != a) {
+                    if distances[a][other] < nearest.1 {
+                        nearest = (other, distances[a][other]);
+                    }
+                }
+                if Some(nearest.0) == previous {
+                    chain.truncate(chain.len() - 2);
+                    break (a, nearest.0);
+                }
+                chain.push(nearest.0);
+            };
+
+            let (keep, remove) = (a.min(b), a.max(b));
+            let d = distances[a][b];
+            merges.push((keep, remove, d));
+            for other in (0..n).filter(|&o| active[o] && o != keep && o != remove) {
+                let (d_keep, d_remove) = (distances[other][keep], distances[other][remove]);
+                let (n_keep, n_remove, n_other) = (sizes[keep] as f32, sizes[remove] as f32, sizes[other] as f32);
+                let updated = match self.linkage {
+                    Linkage::Single => d_keep.min(d_remove),
+                    Linkage::Complete => d_keep.max(d_remove),
+                    Linkage::Average => (n_keep * d_keep + n_remove * d_remove) / (n_keep + n_remove),
+                    Linkage::Ward => (((n_keep + n_other) * d_keep.powi(2) + (n_remove + n_other) * d_remove.powi(2)
+                        - n_other * d.powi(2))
+                        / (n_keep + n_remove + n_other))
+                        .max(0.)
+                        .sqrt(),
+                };
+                distances[other][keep] = updated;
+                distances[keep][other] = updated;
+            }
+            sizes[keep] += sizes[remove];
+            active[remove] = false;
+        }
+
+        merges.sort_by(|a, b| a.2.total_cmp(&b.2));
+        self.dendrogram = Dendrogram::from_edges(n, &merges);
+    }
+
+    /// Merges in the format of SciPy's linkage matrix: row `i` joins clusters `[0]` and `[1]` at
+    /// distance `[2]` into cluster `n + i` of `[3]` rows, ids below `n` being the training rows.
+    pub fn linkage_matrix(&self) -> Vec<[f32; 4]> {
+        self.dendrogram
+            .merges
+            .iter()
+            .enumerate()
+            .map(|(i, &(a, b, d))| [a as f32, b as f32, d, self.dendrogram.sizes[self.dendrogram.n_leaves + i] as f32])
+            .collect()
+    }
+
+    /// Cluster of every training row when the tree is cut into `n_clusters` clusters.
+    pub fn cut(&self, n_clusters: usize) -> Vec<usize> {
+        let n_merges = self.dendrogram.n_leaves.saturating_sub(n_clusters.max(1));
+        self.labels_after(n_merges)
+    }
+
+    /// Cluster of every training row when only merges at most `threshold` apart are made.
+    pub fn cut_at_distance(&self, threshold: f32) -> Vec<usize> {
+        let n_merges = self.dendrogram.merges.iter().take_while(|(_, _, d)| *d <= threshold).count();
+        self.labels_after(n_merges)
+    }
+
+    // Labels when only the first `n_merges` merges are made, numbered in order of their first row.
+    fn labels_after(&self, n_merges: usize) -> Vec<usize> {
+        let n = self.dendrogram.n_leaves;
+        let mut parent: Vec<usize> = (0..n + n_merges).collect();
+        for (i, &(a, b, _)) in self.dendrogram.merges[..n_merges].iter().enumerate() {
+            parent[a] = n + i;
+            parent[b] = n + i;
+        }
+        let roots: Vec<Option<usize>> = (0..n).map(|leaf| Some(find(&mut parent, leaf))).collect();
+        renumber(&roots).into_iter().flatten().collect()
+    }
+}
+
+// Binary merge tree over n leaves: merge i creates node n + i from two nodes at a distance.
+struct Dendrogram {
+    n_leaves: usize,
+    merges: Vec<(usize, usize, f32)>,
+    sizes: Vec<usize>,
+}
+
+impl Dendrogram {
+    // Tree from edges between rows sorted by distance, every edge merging the clusters of its rows.
+    // Spanning tree edges give the single-linkage tree.
+    fn from_edges(n: usize, edges: &[(usize, usize, f32)]) -> Dendrogram {
+        let mut parent: Vec<usize> = (0..(2 * n).saturating_sub(1)).collect();
+        let mut sizes = vec![1; n];
+        let mut merges = Vec::with_capacity(n.saturating_sub(1));
+        for &(a, b, d) in edges {
+            let (root_a, root_b) = (find(&mut parent, a), find(&mut parent, b));
+            let node = n + merges.len();
+            parent[root_a] = node;
+            parent[root_b] = node;
+            sizes.push(sizes[root_a] + sizes[root_b]);
+            merges.push((root_a.min(root_b), root_a.max(root_b), d));
+        }
+        Dendrogram {
+            n_leaves: n,
+            merges,
+            sizes,
+        }
+    }
+
+    fn leaves(&self, node: usize) -> Vec<usize> {
+        let mut leaves = Vec::new();
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if node < self.n_leaves {
+                leaves.push(node);
+            } else {
+                let (a, b, _) = self.merges[node - self.n_leaves];
+                stack.push(b);
+                stack.push(a);
+            }
+        }
+        leaves
+    }
+}
+
+// Root of `node` in a union-find forest, compressing the path on the way.
+fn find(parent: &mut [usize], node: usize) -> usize {
+    let mut root = node;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    let mut node = node;
+    while parent[node] != root {
+        let next = parent[node];
+        parent[node] = root;
+        node = next;
+    }
+    root
+}
+
+// The dendrogram reduced to clusters of at least `min_cluster_size` rows, in lambda = 1 / distance.
+// Cluster 0 is the root, and children always have higher ids than their parent.
+struct Condensed {
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    stabilities: Vec<f32>,
+    // The cluster every row falls out of
+    point_clusters: Vec<usize>,
+}
+
+impl Condensed {
+    fn new(tree: &Dendrogram, min_cluster_size: usize) -> Condensed {
+        let mut condensed = Condensed {
+            parents: vec![None],
+            children: vec![Vec::new()],
+            stabilities: vec![0.],
+            point_clusters: vec![0; tree.n_leaves],
+        };
+        let mut births = vec![0.];
+        let mut stack = vec![(tree.sizes.len() - 1, 0)];
+        while let Some((node, cluster)) = stack.pop() {
+            let (a, b, distance) = tree.merges[node - tree.n_leaves];
+            let lambda = 1. / distance.max(f32::EPSILON);
+            let (size_a, size_b) = (tree.sizes[a], tree.sizes[b]);
+            condensed.stabilities[cluster] += (size_a + size_b) as f32 * (lambda - births[cluster]);
+
+            if size_a >= min_cluster_size && size_b >= min_cluster_size {
+                for child in [a, b] {
+                    let id = condensed.parents.len();
+                    condensed.parents.push(Some(cluster));
+                    condensed.children.push(Vec::new());
+                    condensed.children[cluster].push(id);
+                    condensed.stabilities.push(0.);
+                    births.push(lambda);
+                    stack.push((child, id));
+                }
+            } else {
+                // Small children fall out of the cluster, a large one carries it on
+                for child in [a, b] {
+                    if tree.sizes[child] >= min_cluster_size {
+                        condensed.stabilities[cluster] -= tree.sizes[child] as f32 * (lambda - births[cluster]);
+                        stack.push((child, cluster));
+                    } else {
+                        tree.leaves(child).into_iter().for_each(|p| condensed.point_clusters[p] = cluster);
+                    }
+                }
+            }
+        }
+        condensed
+    }
+
+    // Excess of mass selection: a cluster is kept when it is more stable than its kept descendants
+    // together. The root is never kept, so rows only in the root are noise.
+    fn select(&self) -> Vec<Option<usize>> {
+        let n_clusters = self.parents.len();
+        let mut selected = vec![false; n_clusters];
+        let mut stabilities = self.stabilities.clone();
+        for cluster in (1..n_clusters).rev() {
+            let children: f32 = self.children[cluster].iter().map(|&c| stabilities[c]).sum();
+            if self.children[cluster].is_empty() || self.stabilities[cluster] >= children {
+                selected[cluster] = true;
+                let mut descendants = self.children[cluster].clone();
+                while let Some(d) = descendants.pop() {
+                    selected[d] = false;
+                    descendants.extend(&self.children[d]);
+                }
+            } else {
+                stabilities[cluster] = children;
+            }
+        }
+
+        self.point_clusters
+            .iter()
+            .map(|&cluster| {
+                let mut cluster = Some(cluster);
+                while let Some(c) = cluster {
+                    if selected[c] {
+                        return Some(c);
+                    }
+                    cluster = self.parents[c];
+                }
+                None
+            })
+            .collect()
+    }
+}
+
+// Renumbers cluster labels in order of their first row.
+fn renumber(labels: &[Option<usize>]) -> Vec<Option<usize>> {
+    let mut ids: Vec<usize> = Vec::new();
+    labels
+        .iter()
+        .map(|label| {
+            label.map(|l| match ids.iter().position(|id| *id == l) {
+                Some(i) => i,
+                None => {
+                    ids.push(l);
+                    ids.len() - 1
+                }
+            })
+        })
+        .collect()
+}
+
+// Outcome of a single initialisation.
+struct Run {
+    centers: Vec<Vec<f32>>,
+    inertia: f32,
+    n_iter: usize,
+}
+
+// Panics unless there are enough rows for `n_clusters` centres, all with as many features.
+fn check_clusters(features: &[Vec<f32>], n_clusters: usize) {
+    if n_clusters == 0 || features.len() < n_clusters {
+        panic!("Cannot fit {} clusters to {} rows", n_clusters, features.len());
+    }
+    check_rows(features);
+}
+
+// Picks the first centre uniformly and every next one with probability proportional to the
+// squared distance to the closest centre picked so far.
+fn kmeans_plus_plus<D: Distance>(features: &[Vec<f32>], n_clusters: usize, distance: &D, rng: &mut StdRng) -> Vec<Vec<f32>> {
+    let mut centers = vec![features[rng.gen_range(0..features.len())].clone()];
+    let mut weights: Vec<f32> = features.iter().map(|row| distance.distance(row, &centers[0]).powi(2)).collect();
+    while centers.len() < n_clusters {
+        let total: f32 = weights.iter().sum();
+        let next = if total > 0. {
+            let mut target = rng.gen_range(0.0..total);
+            weights
+                .iter()
+                .position(|&w| {
+                    target -= w;
+                    target < 0. && w > 0.
+                })
+                .unwrap_or_else(|| weights.iter().rposition(|&w| w > 0.).unwrap())
+        } else {
+            rng.gen_range(0..features.len())
+        };
+        centers.push(features[next].clone());
+        for (weight, row) in weights.iter_mut().zip(features.iter()) {
+            *weight = weight.min(distance.distance(row, &features[next]).powi(2));
+        }
+    }
+    centers
+}
+
+// Index of and distance to the centre closest to `row`, the first one on ties.
+fn nearest_center<D: Distance + ?Sized>(centers: &[Vec<f32>], distance: &D, row: &[f32]) -> (usize, f32) {
+    centers
+        .iter()
+        .map(|center| distance.distance(row, center))
+        .enumerate()
+        .fold((0, f32::INFINITY), |best, (i, d)| if d < best.1 { (i, d) } else { best })
 }
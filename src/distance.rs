@@ -0,0 +1,135 @@
+/// A dissimilarity between two feature vectors of equal length.
+pub trait Distance {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32;
+
+    /// Lower bound on the distance between two points whose coordinates differ by `diff` along a
+    /// single axis, used by spatial indices to prune their search. `None` when there is no such bound.
+    fn axis_bound(&self, _diff: f32) -> Option<f32> {
+        None
+    }
+
+    /// Whether the distance satisfies the triangle inequality, which spatial indices rely on to
+    /// prune their search.
+    fn is_metric(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Euclidean;
+
+/// Euclidean distance without the square root; preserves ordering but not the triangle inequality.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SquaredEuclidean;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Manhattan;
+
+/// Largest absolute difference over all features.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Chebyshev;
+
+/// Minkowski distance of order `p`: Manhattan for 1, Euclidean for 2.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Minkowski(pub f32);
+
+/// One minus the cosine of the angle between the vectors, so 0 for equal directions and 2 for
+/// opposite ones. A zero vector is at distance 1 from everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Cosine;
+
+/// Fraction of features that differ.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Hamming;
+
+impl Distance for Euclidean {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        SquaredEuclidean.distance(a, b).sqrt()
+    }
+
+    fn axis_bound(&self, diff: f32) -> Option<f32> {
+        Some(diff)
+    }
+
+    fn is_metric(&self) -> bool {
+        true
+    }
+}
+
+impl Distance for SquaredEuclidean {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum()
+    }
+
+    fn axis_bound(&self, diff: f32) -> Option<f32> {
+        Some(diff * diff)
+    }
+}
+
+impl Distance for Manhattan {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum()
+    }
+
+    fn axis_bound(&self, diff: f32) -> Option<f32> {
+        Some(diff)
+    }
+
+    fn is_metric(&self) -> bool {
+        true
+    }
+}
+
+impl Distance for Chebyshev {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| (x - y).abs()).fold(0., f32::max)
+    }
+
+    fn axis_bound(&self, diff: f32) -> Option<f32> {
+        Some(diff)
+    }
+
+    fn is_metric(&self) -> bool {
+        true
+    }
+}
+
+impl Distance for Minkowski {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).abs().powf(self.0))
+            .sum::<f32>()
+            .powf(1. / self.0)
+    }
+
+    fn axis_bound(&self, diff: f32) -> Option<f32> {
+        Some(diff)
+    }
+
+    fn is_metric(&self) -> bool {
+        self.0 >= 1.
+    }
+}
+
+impl Distance for Cosine {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+        if norms == 0. {
+            1.
+        } else {
+            1. - dot / norms
+        }
+    }
+}
+
+impl Distance for Hamming {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).filter(|(x, y)| x != y).count() as f32 / a.len().max(1) as f32
+    }
+
+    fn is_metric(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,252 @@
+use crate::parallel::map_indices;
+use crate::tree::{most_common, Criterion, Params, Target, Tree};
+use crate::validation::{check_fit, encode_labels};
+use rand::prelude::*;
+use std::hash::Hash;
+
+/// How many features every split of a forest's trees chooses from at random.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaxFeatures {
+    All,
+    Sqrt,
+    Log2,
+    Count(usize),
+}
+
+impl MaxFeatures {
+    fn resolve(&self, n_features: usize) -> usize {
+        let n = match self {
+            MaxFeatures::All => n_features,
+            MaxFeatures::Sqrt => (n_features as f32).sqrt() as usize,
+            MaxFeatures::Log2 => (n_features as f32).log2() as usize,
+            MaxFeatures::Count(count) => *count,
+        };
+        n.clamp(1, n_features.max(1))
+    }
+}
+
+/// Random forest of CART classifiers, predicting the mean class fractions of its trees.
+///
+/// Every tree is grown on a bootstrap sample of the training rows, considering `max_features`
+/// random features per split. Trees are built over `n_threads` threads, a value of 0 or 1 building
+/// on the calling thread; tree `i` draws from a generator seeded with `seed + i`, so a fit is
+/// reproducible regardless of the number of threads.
+pub struct RandomForestClassifier<L> {
+    pub n_trees: usize,
+    pub criterion: Criterion,
+    pub max_depth: Option<usize>,
+    pub min_samples_leaf: usize,
+    pub max_features: MaxFeatures,
+    pub n_threads: usize,
+    pub seed: u64,
+    classes: Vec<L>,
+    trees: Vec<Tree>,
+    oob_error: Option<f32>,
+}
+
+impl<L: Eq + Hash + Clone> RandomForestClassifier<L> {
+    pub fn new(n_trees: usize) -> RandomForestClassifier<L> {
+        RandomForestClassifier {
+            n_trees,
+            criterion: Criterion::Gini,
+            max_depth: None,
+            min_samples_leaf: 1,
+            max_features: MaxFeatures::Sqrt,
+            n_threads: 1,
+            seed: 0,
+            classes: Vec::new(),
+            trees: Vec::new(),
+            oob_error: None,
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], labels: &[L]) {
+        check_fit(features, labels.len());
+        let (classes, class_ids) = encode_labels(labels);
+        let target = Target::Classes(&class_ids, classes.len(), self.criterion);
+        let params = Params {
+            max_depth: self.max_depth,
+            min_samples_leaf: self.min_samples_leaf,
+            max_features: Some(self.max_features.resolve(features.first().map_or(0, |row| row.len()))),
+        };
+        let (trees, in_bag) = grow_forest(features, &target, &params, self.n_trees, self.n_threads, self.seed);
+
+        let oob = oob_predictions(&trees, &in_bag, features);
+        self.oob_error = mean(oob.iter().zip(labels.iter()).filter_map(|(proba, label)| {
+            proba
+                .as_ref()
+                .map(|proba| (most_common(&classes, proba).as_ref() != Some(label)) as u8 as f32)
+        }));
+        self.trees = trees;
+        self.classes = classes;
+    }
+
+    /// Distinct training labels in order of first appearance, the columns of `predict_proba`.
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// Predicts a label per query, `None` when the forest has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<L>> {
+        self.predict_proba(queries)
+            .iter()
+            .map(|proba| most_common(&self.classes, proba))
+            .collect()
+    }
+
+    /// Class fractions per query, averaged over the trees.
+    pub fn predict_proba(&self, queries: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        queries
+            .iter()
+            .map(|query| average(self.trees.iter().map(|tree| tree.leaf(query))))
+            .collect()
+    }
+
+    /// Share of the training rows misclassified by the trees that did not see them, `None` when no
+    /// row was ever left out of a bootstrap sample.
+    pub fn oob_error(&self) -> Option<f32> {
+        self.oob_error
+    }
+
+    /// Feature importances averaged over the trees.
+    pub fn feature_importances(&self) -> Vec<f32> {
+        let importances: Vec<Vec<f32>> = self.trees.iter().map(Tree::feature_importances).collect();
+        average(importances.iter().map(Vec::as_slice))
+    }
+}
+
+/// Random forest of CART regressors, predicting the mean prediction of its trees.
+///
+/// Configured like [`RandomForestClassifier`].
+pub struct RandomForestRegressor {
+    pub n_trees: usize,
+    pub max_depth: Option<usize>,
+    pub min_samples_leaf: usize,
+    pub max_features: MaxFeatures,
+    pub n_threads: usize,
+    pub seed: u64,
+    trees: Vec<Tree>,
+    oob_error: Option<f32>,
+}
+
+impl RandomForestRegressor {
+    pub fn new(n_trees: usize) -> RandomForestRegressor {
+        RandomForestRegressor {
+            n_trees,
+            max_depth: None,
+            min_samples_leaf: 1,
+            max_features: MaxFeatures::All,
+            n_threads: 1,
+            seed: 0,
+            trees: Vec::new(),
+            oob_error: None,
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], targets: &[f32]) {
+        check_fit(features, targets.len());
+        let params = Params {
+            max_depth: self.max_depth,
+            min_samples_leaf: self.min_samples_leaf,
+            max_features: Some(self.max_features.resolve(features.first().map_or(0, |row| row.len()))),
+        };
+        let (trees, in_bag) = grow_forest(features, &Target::Values(targets), &params, self.n_trees, self.n_threads, self.seed);
+
+        let oob = oob_predictions(&trees, &in_bag, features);
+        self.oob_error = mean(
+            oob.iter()
+                .zip(targets.iter())
+                .filter_map(|(prediction, target)| prediction.as_ref().map(|p| (p[0] - target).powi(2))),
+        );
+        self.trees = trees;
+    }
+
+    /// Predicts a value per query, `None` when the forest has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<f32>> {
+        queries
+            .iter()
+            .map(|query| mean(self.trees.iter().filter_map(|tree| tree.leaf(query).first().copied())))
+            .collect()
+    }
+
+    /// Mean squared error on the training rows of the trees that did not see them, `None` when no
+    /// row was ever left out of a bootstrap sample.
+    pub fn oob_error(&self) -> Option<f32> {
+        self.oob_error
+    }
+
+    /// Feature importances averaged over the trees.
+    pub fn feature_importances(&self) -> Vec<f32> {
+        let importances: Vec<Vec<f32>> = self.trees.iter().map(Tree::feature_importances).collect();
+        average(importances.iter().map(Vec::as_slice))
+    }
+}
+
+// Grows every tree on its own bootstrap sample, returning the trees and which rows each one saw.
+fn grow_forest(
+    features: &[Vec<f32>],
+    target: &Target,
+    params: &Params,
+    n_trees: usize,
+    n_threads: usize,
+    seed: u64,
+) -> (Vec<Tree>, Vec<Vec<bool>>) {
+    let n = features.len();
+    map_indices(n_trees, n_threads, |t| {
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(t as u64));
+        let samples: Vec<usize> = if n == 0 {
+            Vec::new()
+        } else {
+            (0..n).map(|_| rng.gen_range(0..n)).collect()
+        };
+        let mut in_bag = vec![false; n];
+        samples.iter().for_each(|&s| in_bag[s] = true);
+        (Tree::fit(features, target, samples, params, Some(&mut rng)), in_bag)
+    })
+    .into_iter()
+    .unzip()
+}
+
+// Leaf values per training row, averaged over the trees that did not see it.
+fn oob_predictions(trees: &[Tree], in_bag: &[Vec<bool>], features: &[Vec<f32>]) -> Vec<Option<Vec<f32>>> {
+    features
+        .iter()
+        .enumerate()
+        .map(|(row, query)| {
+            let leaves: Vec<&[f32]> = trees
+                .iter()
+                .zip(in_bag.iter())
+                .filter(|(_, bag)| !bag[row])
+                .map(|(tree, _)| tree.leaf(query))
+                .collect();
+            if leaves.is_empty() {
+                None
+            } else {
+                Some(average(leaves.into_iter()))
+            }
+        })
+        .collect()
+}
+
+// Element-wise mean of equally long vectors, empty when there are none.
+fn average<'a>(vectors: impl Iterator<Item = &'a [f32]>) -> Vec<f32> {
+    let mut sum: Vec<f32> = Vec::new();
+    let mut count = 0;
+    for vector in vectors {
+        if sum.is_empty() {
+            sum = vec![0.; vector.len()];
+        }
+        sum.iter_mut().zip(vector).for_each(|(s, v)| *s += v);
+        count += 1;
+    }
+    sum.iter().map(|s| s / count as f32).collect()
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0., 0), |(sum, count), value| (sum + value, count + 1));
+    if count == 0 {
+        None
+    } else {
+        Some(sum / count as f32)
+    }
+}
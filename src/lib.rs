@@ -3,14 +3,22 @@ use rand::Rng;
 pub mod apriori;
 pub mod classification;
 pub mod clustering;
+pub mod distance;
 pub mod euler;
+pub mod forest;
 pub mod math;
+pub mod naive_bayes;
 pub mod neuron;
+mod parallel;
 pub mod perceptron;
 pub mod pi;
 pub mod prime;
 pub mod regression;
 pub mod sorting;
+pub mod spatial;
+pub mod svm;
+pub mod tree;
+mod validation;
 
 #[test]
 fn test_reg() {
@@ -45,6 +53,531 @@ fn test_knn() {
     assert_eq!(y_test, y_true);
 }
 
+#[test]
+fn test_knn_classifier() {
+    let features: Vec<Vec<f32>> = vec![
+        vec![0.0, 0.0, 0.0, 1.0],
+        vec![0.5, 0.5, 0.0, 1.0],
+        vec![0.0, 0.5, 0.5, 1.0],
+        vec![3.0, 3.0, 3.0, 0.0],
+        vec![4.0, 3.0, 3.0, 0.0],
+        vec![3.5, 3.5, 4.0, 0.0],
+    ];
+    let labels = vec!["small", "small", "small", "large", "large", "large"];
+
+    let mut knn = classification::KNeighborsClassifier::new(3);
+    assert_eq!(knn.predict(&[vec![0.0, 0.0, 0.0, 0.0]]), vec![None]);
+    knn.fit(&features, &labels);
+    let queries = vec![vec![0.1, 0.2, 0.1, 1.0], vec![3.2, 3.1, 3.3, 0.0], vec![2.0, 2.0, 2.0, 0.5]];
+    assert_eq!(knn.predict(&queries), vec![Some("small"), Some("large"), Some("large")]);
+
+    // With k = 2 the vote ties, so the nearest neighbour decides
+    let mut knn = classification::KNeighborsClassifier::new(2);
+    knn.fit(&[vec![0.0], vec![1.0], vec![3.0]], &[1, 2, 2]);
+    assert_eq!(knn.predict(&[vec![0.4], vec![0.6]]), vec![Some(1), Some(2)]);
+}
+
+#[test]
+fn test_knn_voting() {
+    use classification::{KNeighborsClassifier, TieBreak, Weighting};
+
+    // The old vote picked the largest label among the neighbours rather than the most common one
+    let train: [[f32; 3]; 5] = [[0.0, 0.0, 0.0], [0.1, 0.0, 0.0], [0.0, 0.1, 0.0], [0.3, 0.3, 1.0], [5.0, 5.0, 1.0]];
+    assert_eq!(classification::single_knn(4, &[0.0, 0.0], &train), Some(0.0));
+    assert_eq!(classification::single_knn(5, &[0.0, 0.0], &train), Some(0.0));
+    assert_eq!(classification::single_knn(1, &[0.3, 0.3], &train), Some(1.0));
+    assert_eq!(
+        classification::single_knn_with_distance(1, &[0.3, 0.3], &train, &distance::Manhattan),
+        Some(1.0)
+    );
+
+    let features = vec![vec![0.0], vec![1.0], vec![1.2], vec![4.0], vec![5.0]];
+    let labels = vec!['a', 'b', 'b', 'c', 'c'];
+    let mut knn = KNeighborsClassifier::new(3);
+    knn.fit(&features, &labels);
+    assert_eq!(knn.classes(), &['a', 'b', 'c']);
+    assert_eq!(knn.predict(&[vec![0.1]]), vec![Some('b')]);
+    assert_eq!(knn.predict_proba(&[vec![0.1]]), vec![vec![1. / 3., 2. / 3., 0.]]);
+
+    // a is far closer than both b's, so it wins the inverse-distance vote
+    knn.weighting = Weighting::Distance;
+    assert_eq!(knn.predict(&[vec![0.1]]), vec![Some('a')]);
+    let proba = &knn.predict_proba(&[vec![0.1]])[0];
+    assert!((proba.iter().sum::<f32>() - 1.).abs() < 1e-6 && proba[0] > 0.5);
+    assert_eq!(knn.predict_proba(&[vec![1.0]]), vec![vec![0., 1., 0.]]);
+
+    // A k of 0 is rejected rather than voted on by nobody
+    assert!(std::panic::catch_unwind(|| KNeighborsClassifier::<char>::new(0)).is_err());
+    knn.k = 0;
+    assert!(std::panic::catch_unwind(|| knn.predict(&[vec![0.1]])).is_err());
+    assert!(std::panic::catch_unwind(|| knn.predict_proba(&[vec![0.1]])).is_err());
+
+    // With k = 4 the neighbours of 3.0 are c, b, b, c: a tie whose nearest label is c
+    knn.weighting = Weighting::Uniform;
+    knn.k = 4;
+    let query = vec![vec![3.0]];
+    knn.tie_break = TieBreak::Nearest;
+    assert_eq!(knn.predict(&query), vec![Some('c')]);
+    knn.tie_break = TieBreak::FirstClass;
+    assert_eq!(knn.predict(&query), vec![Some('b')]);
+    knn.tie_break = TieBreak::ReduceK;
+    assert_eq!(knn.predict(&query), vec![Some('b')]);
+}
+
+#[test]
+fn test_distance() {
+    use distance::*;
+
+    let a = [1.0, 2.0, 3.0];
+    let b = [4.0, 0.0, 3.0];
+    assert_eq!(Euclidean.distance(&a, &b), 13f32.sqrt());
+    assert_eq!(SquaredEuclidean.distance(&a, &b), 13.);
+    assert_eq!(Manhattan.distance(&a, &b), 5.);
+    assert_eq!(Chebyshev.distance(&a, &b), 3.);
+    assert!((Minkowski(1.).distance(&a, &b) - 5.).abs() < 1e-5);
+    assert!((Minkowski(2.).distance(&a, &b) - 13f32.sqrt()).abs() < 1e-5);
+    assert!((Minkowski(3.).distance(&a, &b) - 35f32.cbrt()).abs() < 1e-5);
+    assert_eq!(Hamming.distance(&a, &b), 2. / 3.);
+    assert!(Cosine.distance(&a, &[2.0, 4.0, 6.0]).abs() < 1e-6);
+    assert!((Cosine.distance(&[1.0, 0.0], &[0.0, 1.0]) - 1.).abs() < 1e-6);
+    assert!((Cosine.distance(&[1.0, 1.0], &[-1.0, -1.0]) - 2.).abs() < 1e-6);
+
+    // Scaled copies of a direction are identical under cosine but far apart under Euclidean
+    let features = vec![vec![1.0, 0.1], vec![10.0, 1.0], vec![0.1, 1.0], vec![1.0, 10.0]];
+    let mut knn = classification::KNeighborsClassifier::with_distance(1, Cosine);
+    knn.fit(&features, &["x", "x", "y", "y"]);
+    assert_eq!(knn.predict(&[vec![0.5, 4.0], vec![50.0, 4.0]]), vec![Some("y"), Some("x")]);
+    let mut knn = classification::KNeighborsClassifier::new(1);
+    knn.fit(&features, &["x", "x", "y", "y"]);
+    assert_eq!(knn.predict(&[vec![7.0, 4.0]]), vec![Some("x")]);
+}
+
+#[test]
+fn test_spatial() {
+    use distance::*;
+    use rand::prelude::*;
+    use spatial::*;
+
+    let mut rng = StdRng::seed_from_u64(11);
+    let points: Vec<Vec<f32>> = (0..500).map(|_| (0..3).map(|_| rng.gen_range(-10.0..10.0)).collect()).collect();
+    let queries: Vec<Vec<f32>> = (0..25).map(|_| (0..3).map(|_| rng.gen_range(-12.0..12.0)).collect()).collect();
+
+    fn check<D: Distance + Clone>(points: &[Vec<f32>], queries: &[Vec<f32>], distance: D, radius: f32) {
+        let brute = BruteForce::new(points, distance.clone());
+        let kd = KdTree::new(points, distance.clone());
+        let ball = BallTree::new(points, distance);
+        for query in queries.iter() {
+            let nearest = brute.k_nearest(query, 7);
+            assert_eq!(nearest.len(), 7);
+            assert!(nearest.windows(2).all(|w| w[0].1 <= w[1].1));
+            assert_eq!(kd.k_nearest(query, 7), nearest);
+            assert_eq!(ball.k_nearest(query, 7), nearest);
+
+            let within = brute.within_radius(query, radius);
+            assert!(within.iter().all(|(_, d)| *d <= radius));
+            assert_eq!(kd.within_radius(query, radius), within);
+            assert_eq!(ball.within_radius(query, radius), within);
+        }
+        assert_eq!(kd.k_nearest(&queries[0], 1000).len(), points.len());
+    }
+    check(&points, &queries, Euclidean, 4.);
+    check(&points, &queries, SquaredEuclidean, 16.);
+    check(&points, &queries, Manhattan, 6.);
+    check(&points, &queries, Chebyshev, 3.);
+    check(&points, &queries, Minkowski(3.), 4.);
+    check(&points, &queries, Cosine, 0.05);
+
+    // Points on an integer grid, many duplicated, sit exactly on query radii and tie on distances
+    let mut rng = StdRng::seed_from_u64(40);
+    let grid: Vec<Vec<f32>> = (0..200).map(|_| (0..2).map(|_| rng.gen_range(0..10) as f32).collect()).collect();
+    for radius in [1., 2., 3., 5.].iter() {
+        check(&grid, &grid[..40], Euclidean, *radius);
+        check(&grid, &grid[..40], Manhattan, *radius);
+        check(&grid, &grid[..40], Chebyshev, *radius);
+    }
+
+    let empty = KdTree::new(&[], Euclidean);
+    assert!(empty.k_nearest(&[0.0, 0.0], 3).is_empty());
+
+    let labels: Vec<bool> = points.iter().map(|p| p[0] + p[1] > 0.).collect();
+    let mut brute = classification::KNeighborsClassifier::new(5);
+    brute.fit(&points, &labels);
+    for algorithm in [Algorithm::KdTree, Algorithm::BallTree].iter() {
+        let mut knn = classification::KNeighborsClassifier::new(5);
+        knn.algorithm = *algorithm;
+        knn.fit(&points, &labels);
+        assert_eq!(knn.predict(&queries), brute.predict(&queries));
+    }
+}
+
+#[test]
+fn test_knn_regressor() {
+    use classification::{KNeighborsRegressor, Weighting};
+
+    let features = vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![0.0, 1.0], vec![10.0, 10.0]];
+    let targets = vec![1.0, 2.0, 3.0, 100.0];
+
+    let mut knn = KNeighborsRegressor::new(3);
+    assert_eq!(knn.predict(&[vec![0.0, 0.0]]), vec![None]);
+    knn.fit(&features, &targets);
+    assert_eq!(knn.predict(&[vec![0.2, 0.2], vec![9.0, 9.0]]), vec![Some(2.0), Some(35.0)]);
+
+    // Closer neighbours pull the estimate towards their target, exact matches take over
+    knn.weighting = Weighting::Distance;
+    let smoothed = knn.predict(&[vec![0.9, 0.0]])[0].unwrap();
+    assert!(smoothed > 2.0 - 0.1 && smoothed < 2.0);
+    assert_eq!(knn.predict(&[vec![0.0, 1.0]]), vec![Some(3.0)]);
+    assert!(std::panic::catch_unwind(|| KNeighborsRegressor::new(0)).is_err());
+    knn.k = 0;
+    assert!(std::panic::catch_unwind(|| knn.predict(&[vec![0.0, 1.0]])).is_err());
+    assert!(std::panic::catch_unwind(move || knn.fit(&features, &targets)).is_err());
+
+    // Imputing a missing value from rows sharing the other features
+    let mut knn = KNeighborsRegressor::with_distance(2, distance::Manhattan);
+    knn.algorithm = spatial::Algorithm::KdTree;
+    knn.fit(&[vec![1.0], vec![1.1], vec![5.0], vec![5.2]], &[10.0, 12.0, 50.0, 54.0]);
+    assert_eq!(knn.predict(&[vec![1.05], vec![5.1]]), vec![Some(11.0), Some(52.0)]);
+}
+
+#[test]
+fn test_naive_bayes() {
+    use naive_bayes::{BernoulliNB, GaussianNB, MultinomialNB};
+
+    let features = vec![
+        vec![1.0, 20.0],
+        vec![1.2, 22.0],
+        vec![0.8, 19.0],
+        vec![5.0, 20.5],
+        vec![5.3, 21.0],
+        vec![4.8, 19.5],
+    ];
+    let labels = vec!["low", "low", "low", "high", "high", "high"];
+    let mut gaussian = GaussianNB::new();
+    assert_eq!(gaussian.predict(&[vec![1.0, 20.0]]), vec![None]);
+    gaussian.fit(&features, &labels);
+    assert_eq!(gaussian.classes(), &["low", "high"]);
+    assert_eq!(
+        gaussian.predict(&[vec![1.1, 30.0], vec![4.0, 10.0]]),
+        vec![Some("low"), Some("high")]
+    );
+    let proba = &gaussian.predict_proba(&[vec![3.0, 20.0]])[0];
+    assert!((proba.iter().sum::<f32>() - 1.).abs() < 1e-5);
+    assert!(gaussian.predict_proba(&[vec![1.0, 20.0]])[0][0] > 0.99);
+
+    // Baskets as product counts: [bread, butter, beer, crisps]
+    let baskets = vec![
+        vec![2.0, 1.0, 0.0, 0.0],
+        vec![1.0, 2.0, 0.0, 1.0],
+        vec![3.0, 1.0, 0.0, 0.0],
+        vec![0.0, 0.0, 6.0, 2.0],
+        vec![0.0, 1.0, 4.0, 3.0],
+    ];
+    let shoppers = vec!["breakfast", "breakfast", "breakfast", "party", "party"];
+    let mut multinomial = MultinomialNB::new();
+    multinomial.fit(&baskets, &shoppers);
+    assert_eq!(
+        multinomial.predict(&[vec![1.0, 1.0, 0.0, 0.0], vec![0.0, 0.0, 2.0, 1.0]]),
+        vec![Some("breakfast"), Some("party")]
+    );
+    // Smoothing keeps an unseen product from ruling a class out
+    let proba = &multinomial.predict_proba(&[vec![1.0, 0.0, 1.0, 0.0]])[0];
+    assert!(proba.iter().all(|p| *p > 0.));
+
+    let mut bernoulli = BernoulliNB::new();
+    bernoulli.fit(&baskets, &shoppers);
+    assert_eq!(bernoulli.predict(&[vec![1.0, 1.0, 0.0, 0.0]]), vec![Some("breakfast")]);
+    assert_eq!(bernoulli.predict(&[vec![0.0, 0.0, 5.0, 5.0]]), vec![Some("party")]);
+    bernoulli.alpha = 0.;
+    bernoulli.fit(&baskets, &shoppers);
+    assert_eq!(bernoulli.predict_proba(&[vec![0.0, 0.0, 1.0, 1.0]])[0], vec![0., 1.]);
+
+    // Transactions read as baskets are one-hot encoded for the Bernoulli model
+    let db = apriori::read_reader("bread,butter\nbeer,crisps\nbread,jam\n".as_bytes(), apriori::Format::Basket, false).unwrap();
+    let matrix = db.to_matrix();
+    assert_eq!(matrix.len(), 3);
+    assert_eq!(matrix[1], vec![0., 0., 1., 1., 0.]);
+    let mut bernoulli = BernoulliNB::new();
+    bernoulli.fit(&matrix, &["breakfast", "party", "breakfast"]);
+    assert_eq!(bernoulli.predict(&matrix[..2]), vec![Some("breakfast"), Some("party")]);
+}
+
+#[test]
+fn test_tree() {
+    use tree::{Criterion, DecisionTreeClassifier, DecisionTreeRegressor};
+
+    // The label only depends on the first feature, the second is noise
+    let features = vec![
+        vec![1.0, 7.0],
+        vec![2.0, 3.0],
+        vec![3.0, 9.0],
+        vec![4.0, 1.0],
+        vec![5.0, 5.0],
+        vec![6.0, 8.0],
+        vec![7.0, 2.0],
+        vec![8.0, 6.0],
+    ];
+    let labels = vec!["a", "a", "a", "b", "b", "b", "c", "c"];
+
+    let mut clf = DecisionTreeClassifier::new();
+    assert_eq!(clf.predict(&[vec![1.0, 1.0]]), vec![None]);
+    clf.fit(&features, &labels);
+    assert_eq!(clf.predict(&features), labels.iter().map(|l| Some(*l)).collect::<Vec<_>>());
+    assert_eq!(clf.feature_importances(), vec![1., 0.]);
+    assert_eq!(
+        clf.export_rules(Some(&["size", "noise"])),
+        "if size <= 3.5 {\n    \"a\" (100%)\n} else {\n    if size <= 6.5 {\n        \"b\" (100%)\n    } else {\n        \"c\" (100%)\n    }\n}\n"
+    );
+
+    clf.criterion = Criterion::Entropy;
+    clf.max_depth = Some(1);
+    clf.fit(&features, &labels);
+    assert_eq!(clf.predict(&[vec![2.5, 0.0]]), vec![Some("a")]);
+    assert_eq!(clf.predict_proba(&[vec![7.5, 0.0]]), vec![vec![0., 0.6, 0.4]]);
+    assert!(clf.export_rules(None).starts_with("if x[0] <= 3.5 {"));
+
+    // A leaf needs at least three samples, so "c" can never be separated from "b"
+    clf.max_depth = None;
+    clf.min_samples_leaf = 3;
+    clf.fit(&features, &labels);
+    assert_eq!(clf.predict(&[vec![8.0, 6.0]]), vec![Some("b")]);
+
+    // Adjacent floats have no midpoint, the lower one becomes the threshold
+    let (low, high) = (1.0f32, f32::from_bits(1.0f32.to_bits() + 1));
+    clf.min_samples_leaf = 1;
+    clf.fit(&[vec![low], vec![high]], &["a", "b"]);
+    assert_eq!(clf.predict(&[vec![low], vec![high]]), vec![Some("a"), Some("b")]);
+    assert_eq!(
+        clf.export_rules(None),
+        "if x[0] <= 1 {\n    \"a\" (100%)\n} else {\n    \"b\" (100%)\n}\n"
+    );
+
+    let targets: Vec<f32> = features.iter().map(|row| if row[0] < 4.5 { 1.0 } else { 10.0 + row[0] }).collect();
+    let mut reg = DecisionTreeRegressor::new();
+    reg.fit(&features, &targets);
+    assert_eq!(reg.predict(&features), targets.iter().map(|t| Some(*t)).collect::<Vec<_>>());
+    assert_eq!(reg.feature_importances()[1], 0.);
+
+    reg.max_depth = Some(1);
+    reg.fit(&features, &targets);
+    assert_eq!(reg.predict(&[vec![0.0, 0.0], vec![9.0, 0.0]]), vec![Some(1.0), Some(16.5)]);
+    assert_eq!(reg.export_rules(None), "if x[0] <= 4.5 {\n    1\n} else {\n    16.5\n}\n");
+
+    // Targets far from zero still split, the variance keeping its precision
+    let offset: Vec<Vec<f32>> = (0..20).map(|i| vec![i as f32]).collect();
+    let offset_targets: Vec<f32> = (0..20).map(|i| 100_000. + 10. * (i / 5) as f32).collect();
+    let mut reg = DecisionTreeRegressor::new();
+    reg.fit(&offset, &offset_targets);
+    assert_eq!(reg.predict(&offset), offset_targets.iter().map(|t| Some(*t)).collect::<Vec<_>>());
+
+    // Alternating labels grow a tree a level per row, which exports without deep recursion
+    let chain: Vec<Vec<f32>> = (0..1000).map(|i| vec![i as f32]).collect();
+    let mut clf = DecisionTreeClassifier::new();
+    clf.fit(&chain, &(0..1000).map(|i| i % 2).collect::<Vec<_>>());
+    let rules = std::thread::Builder::new()
+        .stack_size(64 * 1024)
+        .spawn(move || clf.export_rules(None))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(rules.lines().count(), 3 * 999 + 1000);
+}
+
+#[test]
+fn test_random_forest() {
+    use forest::{MaxFeatures, RandomForestClassifier, RandomForestRegressor};
+    use rand::prelude::*;
+
+    // The label is decided by the first two features, the last three are noise
+    let mut rng = StdRng::seed_from_u64(5);
+    let features: Vec<Vec<f32>> = (0..200).map(|_| (0..5).map(|_| rng.gen_range(-1.0..1.0)).collect()).collect();
+    let labels: Vec<bool> = features.iter().map(|row| row[0] + row[1] > 0.).collect();
+    let queries = vec![vec![0.8, 0.6, 0.0, 0.0, 0.0], vec![-0.7, -0.5, 0.9, -0.9, 0.9]];
+
+    let mut clf = RandomForestClassifier::new(25);
+    assert_eq!(clf.predict(&queries), vec![None, None]);
+    assert_eq!(clf.oob_error(), None);
+    clf.fit(&features, &labels);
+    assert_eq!(clf.predict(&queries), vec![Some(true), Some(false)]);
+    assert!(clf.oob_error().unwrap() < 0.15);
+    let importances = clf.feature_importances();
+    assert!(importances[0] > 0.25 && importances[1] > 0.25 && importances[2..].iter().all(|i| *i < 0.15));
+
+    // Every tree seeds its own generator, so threads do not change the result
+    let mut threaded = RandomForestClassifier::new(25);
+    threaded.n_threads = 4;
+    threaded.fit(&features, &labels);
+    assert_eq!(threaded.predict_proba(&features), clf.predict_proba(&features));
+    assert_eq!(threaded.oob_error(), clf.oob_error());
+    threaded.seed = 1;
+    threaded.fit(&features, &labels);
+    assert_ne!(threaded.predict_proba(&features), clf.predict_proba(&features));
+
+    let targets: Vec<f32> = features.iter().map(|row| 3. * row[0] - row[1]).collect();
+    let mut reg = RandomForestRegressor::new(25);
+    reg.n_threads = 3;
+    reg.fit(&features, &targets);
+    let predictions = reg.predict(&queries);
+    assert!((predictions[0].unwrap() - 1.8).abs() < 0.5 && (predictions[1].unwrap() + 1.6).abs() < 0.5);
+    assert!(reg.oob_error().unwrap() < 0.1);
+    reg.max_features = MaxFeatures::Count(2);
+    reg.fit(&features, &targets);
+    assert!(reg.oob_error().unwrap() > 0.1);
+
+    // The same targets far from zero give the same fit, shifted
+    let mut shifted = RandomForestRegressor::new(25);
+    shifted.fit(&features, &targets.iter().map(|t| t + 100_000.).collect::<Vec<_>>());
+    let predictions = shifted.predict(&queries);
+    assert!((predictions[0].unwrap() - 100_001.8).abs() < 0.5 && (predictions[1].unwrap() - 99_998.4).abs() < 0.5);
+    assert!(shifted.oob_error().unwrap() < 0.1);
+
+    // Rows one ulp apart are split between them on every bootstrap sample
+    let near: Vec<Vec<f32>> = (0..40).map(|i| vec![f32::from_bits(1.0f32.to_bits() + i)]).collect();
+    let halves: Vec<bool> = (0..40).map(|i| i >= 20).collect();
+    let mut clf = RandomForestClassifier::new(10);
+    clf.n_threads = 2;
+    clf.fit(&near, &halves);
+    assert_eq!(clf.predict(&near), halves.iter().map(|h| Some(*h)).collect::<Vec<_>>());
+    let mut reg = RandomForestRegressor::new(10);
+    reg.fit(&near, &halves.iter().map(|h| *h as u8 as f32).collect::<Vec<_>>());
+    assert_eq!(reg.predict(&near[..1]), vec![Some(0.)]);
+}
+
+#[test]
+fn test_logistic_regression() {
+    use rand::prelude::*;
+    use regression::{LogisticRegression, Penalty};
+
+    // Two classes split by the line x0 + x1 = 0, the third feature is noise
+    let mut rng = StdRng::seed_from_u64(9);
+    let features: Vec<Vec<f32>> = (0..200).map(|_| (0..3).map(|_| rng.gen_range(-1.0..1.0)).collect()).collect();
+    let labels: Vec<&str> = features.iter().map(|row| if row[0] + row[1] > 0. { "yes" } else { "no" }).collect();
+    let queries = vec![vec![0.5, 0.5, 0.0], vec![-0.5, -0.2, 0.9]];
+
+    let mut model = LogisticRegression::new();
+    assert_eq!(model.predict(&queries), vec![None, None]);
+    model.fit(&features, &labels);
+    assert_eq!(model.predict(&queries), vec![Some("yes"), Some("no")]);
+    // The sigmoid models the second class seen, here "no"
+    assert_eq!(model.classes(), &["yes", "no"]);
+    assert_eq!(model.coefficients().len(), 1);
+    let coefficients = &model.coefficients()[0];
+    assert!(coefficients[0] < -2. && coefficients[1] < -2. && coefficients[2].abs() < 1.);
+    let proba = model.predict_proba(&queries);
+    assert!(proba[0][0] > 0.9 && (proba[0][0] + proba[0][1] - 1.).abs() < 1e-6);
+
+    // Mini-batches take many more steps, L2 keeps the coefficients smaller
+    model.batch_size = Some(10);
+    model.epochs = 100;
+    model.fit(&features, &labels);
+    let accuracy = model
+        .predict(&features)
+        .iter()
+        .zip(labels.iter())
+        .filter(|(p, l)| p.as_ref() == Some(l))
+        .count();
+    assert!(accuracy >= 195);
+    let unpenalised: f32 = model.coefficients()[0].iter().map(|w| w.powi(2)).sum();
+    model.penalty = Penalty::L2(0.1);
+    model.fit(&features, &labels);
+    assert!(model.coefficients()[0].iter().map(|w| w.powi(2)).sum::<f32>() < unpenalised / 4.);
+
+    // L1 drops the noise feature entirely
+    model.batch_size = None;
+    model.epochs = 1000;
+    model.penalty = Penalty::L1(0.02);
+    model.fit(&features, &labels);
+    assert_eq!(model.coefficients()[0][2], 0.);
+    assert_eq!(model.predict(&queries), vec![Some("yes"), Some("no")]);
+
+    // Three classes get a softmax with a row of coefficients each
+    let labels: Vec<usize> = features
+        .iter()
+        .map(|row| {
+            if row[0] > 0.3 {
+                0
+            } else if row[1] > 0. {
+                1
+            } else {
+                2
+            }
+        })
+        .collect();
+    let mut model = LogisticRegression::new();
+    model.fit(&features, &labels);
+    assert_eq!(model.classes().len(), 3);
+    assert_eq!(model.coefficients().len(), 3);
+    assert_eq!(model.intercepts().len(), 3);
+    assert_eq!(
+        model.predict(&[vec![0.9, 0.0, 0.0], vec![-0.5, 0.8, 0.0], vec![-0.5, -0.8, 0.0]]),
+        vec![Some(0), Some(1), Some(2)]
+    );
+}
+
+#[test]
+fn test_svm() {
+    use svm::{Kernel, SVC};
+
+    // The widest margin lies between the lines x + y = 1 and x + y = 4
+    let features = vec![
+        vec![0.0, 0.0],
+        vec![1.0, 0.0],
+        vec![0.0, 1.0],
+        vec![2.0, 2.0],
+        vec![3.0, 2.0],
+        vec![2.0, 3.0],
+    ];
+    let labels = vec!['a', 'a', 'a', 'b', 'b', 'b'];
+    let mut svc = SVC::new(Kernel::Linear);
+    assert_eq!(svc.predict(&[vec![0.0, 0.0]]), vec![None]);
+    svc.c = 100.;
+    svc.fit(&features, &labels);
+    assert_eq!(svc.support(), &[1, 2, 3]);
+    assert_eq!(svc.support_vectors(), &features[1..4]);
+    let scores = svc.decision_function(&[vec![1.0, 0.0], vec![2.0, 2.0], vec![0.75, 0.75]]);
+    for (score, expected) in scores.iter().zip([-1.0, 1.0, -2. / 3.].iter()) {
+        assert_eq!(score.len(), 1);
+        assert!((score[0] - expected).abs() < 1e-3);
+    }
+    assert_eq!(svc.predict(&[vec![1.2, 1.2], vec![1.3, 1.3]]), vec![Some('a'), Some('b')]);
+
+    // An inner and an outer ring cannot be split by a line
+    let rings: Vec<Vec<f32>> = (0..16)
+        .map(|i| {
+            let (angle, radius) = (i as f32 * std::f32::consts::PI / 4., if i < 8 { 0.5 } else { 2.0 });
+            vec![radius * angle.cos(), radius * angle.sin()]
+        })
+        .collect();
+    let labels: Vec<char> = (0..16).map(|i| if i < 8 { 'i' } else { 'o' }).collect();
+    let expected: Vec<Option<char>> = labels.iter().map(|l| Some(*l)).collect();
+    let queries = vec![vec![0.1, -0.2], vec![-1.9, 0.5]];
+    svc.kernel = Kernel::Rbf { gamma: 1. };
+    svc.fit(&rings, &labels);
+    assert_eq!(svc.predict(&rings), expected);
+    assert_eq!(svc.predict(&queries), vec![Some('i'), Some('o')]);
+    svc.kernel = Kernel::Polynomial {
+        degree: 2,
+        gamma: 1.,
+        coef0: 1.,
+    };
+    svc.fit(&rings, &labels);
+    assert_eq!(svc.predict(&rings), expected);
+    svc.kernel = Kernel::Linear;
+    svc.fit(&rings, &labels);
+    assert_ne!(svc.predict(&rings), expected);
+
+    // Three classes are separated one-vs-rest, with a score per class
+    let centres = [[0.0, 0.0], [5.0, 0.0], [0.0, 5.0]];
+    let features: Vec<Vec<f32>> = (0..15)
+        .map(|i| vec![centres[i % 3][0] + (i / 3) as f32 * 0.2, centres[i % 3][1] - (i / 3) as f32 * 0.1])
+        .collect();
+    let labels: Vec<usize> = (0..15).map(|i| i % 3).collect();
+    let mut svc = SVC::new(Kernel::Linear);
+    svc.fit(&features, &labels);
+    let queries: Vec<Vec<f32>> = centres.iter().map(|c| c.to_vec()).collect();
+    assert_eq!(svc.predict(&queries), vec![Some(0), Some(1), Some(2)]);
+    assert!(svc.decision_function(&queries).iter().all(|scores| scores.len() == 3));
+}
+
 #[test]
 fn test_pi() {
     let pi: f64 = pi::pi_random_numbers(10000);
@@ -61,7 +594,9 @@ fn test_pi() {
 fn test_apriori() {
     use std::collections::HashSet;
 
-    let transactions: Vec<HashSet<String>> = apriori::read_file("./data/store_data.csv");
+    let transactions: Vec<HashSet<String>> = apriori::read_file("./data/store_data.csv", apriori::Format::Basket, true)
+        .unwrap()
+        .transactions();
     println!("{:?}", transactions[4]);
     println!("{:?}", transactions[10]);
 
@@ -84,6 +619,357 @@ fn test_apriori() {
     println!("Max Lift:   {} with {:?}", max_lift, max_prod);
 }
 
+#[test]
+fn test_apriori_mine() {
+    use std::collections::HashSet;
+
+    let transactions: Vec<HashSet<String>> = vec![
+        vec!["bread", "milk"],
+        vec!["bread", "diaper", "beer", "eggs"],
+        vec!["milk", "diaper", "beer", "cola"],
+        vec!["bread", "milk", "diaper", "beer"],
+        vec!["bread", "milk", "diaper", "cola"],
+    ]
+    .into_iter()
+    .map(|t| t.into_iter().map(String::from).collect())
+    .collect();
+
+    let itemsets = apriori::mine(&transactions, 0.6);
+    let items: Vec<Vec<&str>> = itemsets.iter().map(|s| s.items.iter().map(|p| p.as_str()).collect()).collect();
+    assert_eq!(
+        items,
+        vec![
+            vec!["beer"],
+            vec!["bread"],
+            vec!["diaper"],
+            vec!["milk"],
+            vec!["beer", "diaper"],
+            vec!["bread", "diaper"],
+            vec!["bread", "milk"],
+            vec!["diaper", "milk"],
+        ]
+    );
+    for itemset in itemsets.iter() {
+        let sup = apriori::support(itemset.items.iter().cloned().collect(), &transactions);
+        assert!((itemset.support - sup).abs() < 1e-6);
+    }
+
+    assert_eq!(apriori::mine(&transactions, 0.4).iter().filter(|s| s.items.len() == 3).count(), 4);
+    assert!(apriori::mine(&[], 0.5).is_empty());
+}
+
+#[test]
+fn test_apriori_rules() {
+    use std::collections::HashSet;
+
+    let transactions: Vec<HashSet<String>> = vec![
+        vec!["bread", "butter", "milk"],
+        vec!["bread", "butter", "milk"],
+        vec!["bread", "butter"],
+        vec!["bread", "milk"],
+        vec!["milk"],
+    ]
+    .into_iter()
+    .map(|t| t.into_iter().map(String::from).collect())
+    .collect();
+
+    let itemsets = apriori::mine(&transactions, 0.4);
+    let rules = apriori::rules(&itemsets, 0.6, 0., apriori::Metric::Confidence);
+    assert!(rules.windows(2).all(|w| w[0].confidence >= w[1].confidence));
+    assert!(rules.iter().all(|r| r.confidence >= 0.6));
+
+    let rule = rules
+        .iter()
+        .find(|r| r.antecedent == vec!["bread", "butter"] && r.consequent == vec!["milk"])
+        .unwrap();
+    assert!((rule.support - 0.4).abs() < 1e-6);
+    assert!((rule.confidence - 2. / 3.).abs() < 1e-6);
+    assert!((rule.lift - (2. / 3.) / 0.8).abs() < 1e-6);
+    assert!((rule.leverage - (0.4 - 0.6 * 0.8)).abs() < 1e-6);
+    assert!((rule.conviction - 0.2 / (1. / 3.)).abs() < 1e-5);
+
+    let lift = apriori::lift(
+        ["bread", "butter"].iter().map(|p| p.to_string()).collect(),
+        ["milk"].iter().map(|p| p.to_string()).collect(),
+        &transactions,
+    );
+    assert!((rule.lift - lift).abs() < 1e-6);
+
+    // {butter} -> {bread} always holds
+    let rule = rules
+        .iter()
+        .find(|r| r.antecedent == vec!["butter"] && r.consequent == vec!["bread"])
+        .unwrap();
+    assert_eq!(rule.conviction, f32::INFINITY);
+
+    assert!(apriori::rules(&itemsets, 0., 1.1, apriori::Metric::Lift)
+        .iter()
+        .all(|r| r.lift >= 1.1));
+}
+
+#[test]
+fn test_apriori_closed_maximal() {
+    use std::collections::HashSet;
+
+    let transactions: Vec<HashSet<String>> = vec![
+        vec!["bread", "butter", "milk"],
+        vec!["bread", "butter", "milk"],
+        vec!["bread", "butter"],
+        vec!["bread", "milk"],
+        vec!["eggs"],
+    ]
+    .into_iter()
+    .map(|t| t.into_iter().map(String::from).collect())
+    .collect();
+
+    let itemsets = apriori::mine(&transactions, 0.4);
+    let items = |itemsets: Vec<apriori::Itemset>| -> Vec<Vec<String>> { itemsets.into_iter().map(|s| s.items).collect() };
+
+    // {butter} always comes with bread, so it is not closed
+    assert_eq!(
+        items(apriori::closed(&itemsets)),
+        vec![
+            vec!["bread"],
+            vec!["bread", "butter"],
+            vec!["bread", "milk"],
+            vec!["bread", "butter", "milk"],
+        ]
+    );
+    assert_eq!(items(apriori::maximal(&itemsets)), vec![vec!["bread", "butter", "milk"]]);
+
+    let closed = apriori::closed(&itemsets);
+    assert!(apriori::maximal(&itemsets).iter().all(|m| closed.contains(m)));
+}
+
+#[test]
+fn test_apriori_fp_growth() {
+    use rand::prelude::*;
+    use std::collections::HashSet;
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let products = ["apples", "bread", "butter", "cheese", "eggs", "milk", "pasta", "wine"];
+    let transactions: Vec<HashSet<String>> = (0..200)
+        .map(|_| products.iter().filter(|_| rng.gen_bool(0.4)).map(|p| p.to_string()).collect())
+        .collect();
+
+    for min_support in [0.05, 0.1, 0.2, 0.3].iter() {
+        let itemsets = apriori::fp_growth(&transactions, *min_support);
+        assert!(!itemsets.is_empty());
+        assert_eq!(itemsets, apriori::mine(&transactions, *min_support));
+    }
+    assert!(apriori::fp_growth(&transactions, 1.1).is_empty());
+}
+
+#[test]
+fn test_apriori_eclat() {
+    use rand::prelude::*;
+    use std::collections::HashSet;
+
+    let mut rng = StdRng::seed_from_u64(3);
+    let products = ["apples", "bread", "butter", "cheese", "eggs", "milk", "pasta", "wine", "yoghurt"];
+    let transactions: Vec<HashSet<String>> = (0..300)
+        .map(|_| products.iter().filter(|_| rng.gen_bool(0.45)).map(|p| p.to_string()).collect())
+        .collect();
+
+    let db = apriori::TransactionDb::new(&transactions);
+    for min_support in [0.05, 0.15, 0.3].iter() {
+        let itemsets = apriori::mine(&transactions, *min_support);
+        assert!(!itemsets.is_empty());
+        assert_eq!(apriori::eclat(&db, *min_support, 1), itemsets);
+        assert_eq!(apriori::eclat(&db, *min_support, 4), itemsets);
+        assert_eq!(apriori::eclat(&db, *min_support, 1000), itemsets);
+    }
+    assert!(apriori::eclat(&apriori::TransactionDb::new(&[]), 0.1, 2).is_empty());
+}
+
+#[test]
+fn test_apriori_transaction_db() {
+    use rand::prelude::*;
+    use std::collections::HashSet;
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let products = ["apples", "bread", "butter", "cheese", "eggs", "milk"];
+    let transactions: Vec<HashSet<String>> = (0..150)
+        .map(|_| products.iter().filter(|_| rng.gen_bool(0.5)).map(|p| p.to_string()).collect())
+        .collect();
+    let db = apriori::TransactionDb::new(&transactions);
+    assert_eq!(db.len(), 150);
+    assert_eq!(db.transactions(), transactions);
+
+    let set = |items: &[&str]| -> HashSet<String> { items.iter().map(|p| p.to_string()).collect() };
+    let a = set(&["bread", "butter"]);
+    let b = set(&["milk"]);
+    assert!((db.support(&a) - apriori::support(a.clone(), &transactions)).abs() < 1e-6);
+    assert!((db.confidence(&a, &b) - apriori::confidence(a.clone(), b.clone(), &transactions)).abs() < 1e-6);
+    assert!((db.lift(&a, &b) - apriori::lift(a.clone(), b.clone(), &transactions)).abs() < 1e-5);
+    assert_eq!(db.support(&set(&[])), 1.);
+    assert_eq!(db.support(&set(&["bread", "caviar"])), 0.);
+}
+
+#[test]
+fn test_apriori_read() {
+    use std::collections::HashSet;
+
+    let set = |items: &[&str]| -> HashSet<String> { items.iter().map(|p| p.to_string()).collect() };
+
+    let basket = "products\r\nbread, milk\r\n\r\n\"wine, red\",cheese,,\r\n , \n  eggs  \n";
+    let db = apriori::read_reader(basket.as_bytes(), apriori::Format::Basket, true).unwrap();
+    assert_eq!(
+        db.transactions(),
+        vec![set(&["bread", "milk"]), set(&["wine, red", "cheese"]), set(&["eggs"])]
+    );
+
+    let long = "1,bread\n2,milk\n1,butter\n\n3, eggs\n , \n2,bread\n,\n";
+    let db = apriori::read_reader(long.as_bytes(), apriori::Format::Long, false).unwrap();
+    assert_eq!(
+        db.transactions(),
+        vec![set(&["bread", "butter"]), set(&["milk", "bread"]), set(&["eggs"])]
+    );
+
+    let missing = "transaction_id,item\n1,bread\n2\n";
+    match apriori::read_reader(missing.as_bytes(), apriori::Format::Long, true) {
+        Err(apriori::Error::MissingField { line }) => assert_eq!(line, 3),
+        other => panic!("expected a missing field, got {:?}", other),
+    }
+    assert!(apriori::read_file("./does/not/exist.csv", apriori::Format::Basket, true).is_err());
+}
+
+#[test]
+fn test_apriori_recommend() {
+    use std::collections::HashSet;
+
+    let set = |items: &[&str]| -> HashSet<String> { items.iter().map(|p| p.to_string()).collect() };
+    let transactions = vec![
+        set(&["bread", "butter", "milk"]),
+        set(&["bread", "butter", "jam"]),
+        set(&["bread", "butter", "jam"]),
+        set(&["bread", "milk"]),
+        set(&["milk", "cereal"]),
+        set(&["jam"]),
+    ];
+    let db = apriori::TransactionDb::new(&transactions);
+
+    let recommended = apriori::recommend(&set(&["bread", "butter"]), &db, 5, apriori::Metric::Confidence);
+    assert_eq!(recommended.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>(), vec!["jam", "milk"]);
+    assert!((recommended[0].1 - 2. / 3.).abs() < 1e-6);
+
+    let recommended = apriori::recommend(&set(&["bread"]), &db, 1, apriori::Metric::Lift);
+    assert_eq!(recommended.len(), 1);
+    assert_eq!(recommended[0].0, "butter");
+    let lift = db.lift(&set(&["bread"]), &set(&["butter"]));
+    assert!((recommended[0].1 - lift).abs() < 1e-6);
+
+    let (max_lift, max_prod) = apriori::best_lift(set(&["bread", "milk"]), &transactions);
+    assert_eq!(max_prod, set(&["butter"]));
+    assert!((max_lift - db.lift(&set(&["bread", "milk"]), &set(&["butter"]))).abs() < 1e-6);
+
+    assert!(apriori::recommend(&set(&["caviar"]), &db, 3, apriori::Metric::Support).is_empty());
+}
+
+#[test]
+fn test_apriori_prefix_span() {
+    use std::collections::HashSet;
+
+    // Example database from the PrefixSpan paper (Pei et al., 2001)
+    let data = "customer,time,item\n\
+                1,1,a\n1,2,a\n1,2,b\n1,2,c\n1,3,a\n1,3,c\n1,4,d\n1,5,c\n1,5,f\n\
+                ,,\n2,1,a\n2,1,d\n2,2,c\n2,3,b\n2,3,c\n2,4,a\n2,4,e\n\
+                3,1,e\n3,1,f\n3,2,a\n3,2,b\n3,3,d\n3,3,f\n3,4,c\n3,5,b\n\
+                4,1,e\n4,2,g\n4,3,a\n4,3,f\n4,4,c\n4,5,b\n4,6,c\n";
+    let db = apriori::read_sequences_reader(data.as_bytes(), true).unwrap();
+    assert_eq!(db.len(), 4);
+    assert_eq!(db.sequence("2").unwrap().len(), 4);
+
+    let patterns = apriori::prefix_span(&db, 0.5);
+    assert_eq!(patterns.len(), 53);
+
+    let singles: Vec<(&str, f32)> = patterns
+        .iter()
+        .filter(|p| p.sequence.len() == 1 && p.sequence[0].len() == 1)
+        .map(|p| (p.sequence[0][0].as_str(), p.support))
+        .collect();
+    assert_eq!(
+        singles,
+        vec![("a", 1.), ("b", 1.), ("c", 1.), ("d", 0.75), ("e", 0.75), ("f", 0.75)]
+    );
+
+    let pattern: Vec<HashSet<String>> = vec![
+        ["a", "b"].iter().map(|p| p.to_string()).collect(),
+        ["c"].iter().map(|p| p.to_string()).collect(),
+    ];
+    let found = patterns.iter().find(|p| p.sequence == vec![vec!["a", "b"], vec!["c"]]).unwrap();
+    assert_eq!(found.support, 0.5);
+
+    for p in patterns.iter() {
+        let sequence: Vec<HashSet<String>> = p.sequence.iter().map(|itemset| itemset.iter().cloned().collect()).collect();
+        assert_eq!(db.support(&sequence), p.support);
+    }
+    assert_eq!(db.confidence(&pattern[..1], &pattern[1..]), 1.);
+    assert!(apriori::prefix_span(&db, 1.)
+        .iter()
+        .all(|p| p.sequence.len() == 1 || p.support == 1.));
+}
+
+#[test]
+fn test_apriori_utility() {
+    use std::collections::{HashMap, HashSet};
+
+    let set = |items: &[&str]| -> HashSet<String> { items.iter().map(|p| p.to_string()).collect() };
+    let transactions = vec![
+        set(&["a", "b", "c"]),
+        set(&["a", "c", "d"]),
+        set(&["b", "c"]),
+        set(&["a", "b", "d"]),
+    ];
+
+    let db = apriori::TransactionDb::new(&transactions);
+    let ones = vec![1.; transactions.len()];
+    let (a, c) = (set(&["a"]), set(&["c"]));
+    assert_eq!(db.weighted_support(&a, &ones), db.support(&a));
+    assert_eq!(db.weighted_lift(&a, &c, &ones), db.lift(&a, &c));
+
+    let weights = vec![8., 17., 7., 9.];
+    assert!((db.weighted_support(&set(&["a", "c"]), &weights) - 25. / 41.).abs() < 1e-6);
+    assert!((db.weighted_confidence(&a, &c, &weights) - 25. / 34.).abs() < 1e-6);
+    assert_eq!(db.weighted_support(&set(&["a", "e"]), &weights), 0.);
+    assert!(std::panic::catch_unwind(|| db.weighted_support(&a, &weights[1..])).is_err());
+
+    let utilities: Vec<HashMap<String, f32>> = vec![
+        vec![("a", 5.), ("b", 2.), ("c", 1.)],
+        vec![("a", 10.), ("c", 6.), ("d", 1.)],
+        vec![("b", 4.), ("c", 3.)],
+        vec![("a", 2.), ("b", 2.), ("d", 5.)],
+    ]
+    .into_iter()
+    .map(|t| t.into_iter().map(|(p, u)| (p.to_string(), u)).collect())
+    .collect();
+    assert_eq!(apriori::utility(&set(&["a", "c"]), &utilities), 22.);
+
+    // Every subset of {a, b, c, d} by brute force
+    let products = ["a", "b", "c", "d"];
+    let mut expected: Vec<(Vec<String>, f32)> = (1..16u32)
+        .map(|mask| {
+            let items: Vec<String> = products
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, p)| p.to_string())
+                .collect();
+            let utility = apriori::utility(&items.iter().cloned().collect(), &utilities);
+            (items, utility)
+        })
+        .filter(|(_, utility)| *utility >= 12.)
+        .collect();
+    expected.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+    let mined: Vec<(Vec<String>, f32)> = apriori::high_utility_itemsets(&utilities, 12.)
+        .into_iter()
+        .map(|s| (s.items, s.utility))
+        .collect();
+    assert_eq!(mined, expected);
+    assert!(mined.iter().any(|(items, _)| items.len() > 1));
+}
+
 #[test]
 fn test_selection_sort() {
     let mut arr1: [i64; 4] = [21, -4, 6, 12];
@@ -254,11 +1140,251 @@ fn test_euler() {
 
 #[test]
 fn test_kmeans() {
+    // Three blobs far outside [0, 1), four rows each
+    let x: Vec<Vec<f32>> = vec![
+        vec![100.0, 100.0],
+        vec![101.0, 100.0],
+        vec![100.0, 101.0],
+        vec![101.0, 101.0],
+        vec![-50.0, 20.0],
+        vec![-52.0, 20.0],
+        vec![-50.0, 22.0],
+        vec![-52.0, 22.0],
+        vec![0.0, -80.0],
+        vec![0.0, -81.0],
+        vec![1.0, -80.0],
+        vec![1.0, -81.0],
+    ];
+    let mut kmeans = clustering::KMeans::new(3);
+    kmeans.fit(&x);
+    let pred = kmeans.predict(&x);
+    for blob in pred.chunks(4) {
+        assert!(blob.iter().all(|c| *c == blob[0]));
+    }
+    assert_ne!(pred[0], pred[4]);
+    assert_ne!(pred[4], pred[8]);
+    assert_ne!(pred[0], pred[8]);
+    assert_eq!(kmeans.cluster_centers()[pred[4]], vec![-51.0, 21.0]);
+    assert!((kmeans.inertia() - 4. * (0.5 + 2. + 0.5)).abs() < 1e-3);
+    assert!(kmeans.n_iter() < kmeans.max_iter);
+    assert_eq!(kmeans.predict(&[vec![90.0, 90.0], vec![5.0, -70.0]]), vec![pred[0], pred[8]]);
+
+    // Two clusters can only split the blobs, and the fit is reproducible
     let mut kmeans = clustering::KMeans::new(2);
-    let x: Vec<Vec<f32>> = vec![vec![0.0, 1.0], vec![2.0, 3.0], vec![4.0, 5.0], vec![0.06, 7.0]];
-    kmeans.fit(&x, 10);
-    let pred = kmeans.predict(x);
-    println!("{:?}", pred)
+    kmeans.fit(&x);
+    let pred = kmeans.predict(&x);
+    kmeans.fit(&x);
+    assert_eq!(kmeans.predict(&x), pred);
+    assert!(kmeans.inertia() > 1000.);
+
+    // As many clusters as rows gives every row its own centre
+    let mut kmeans = clustering::KMeans::new(12);
+    kmeans.fit(&x);
+    assert_eq!(kmeans.inertia(), 0.);
+    let mut pred = kmeans.predict(&x);
+    pred.sort_unstable();
+    assert_eq!(pred, (0..12).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_kmeans_n_init() {
+    use clustering::KMeans;
+    use rand::prelude::*;
+
+    // Eight loose blobs, where single initialisations often settle in a local optimum
+    let mut rng = StdRng::seed_from_u64(8);
+    let x: Vec<Vec<f32>> = (0..160)
+        .map(|i| {
+            vec![
+                (i % 4) as f32 * 4. + rng.gen_range(-1.5..1.5),
+                (i % 8 / 4) as f32 * 4. + rng.gen_range(-1.5..1.5),
+            ]
+        })
+        .collect();
+
+    // Run i of a restarted fit is the single fit seeded with seed + i
+    let inertias: Vec<f32> = (0..10)
+        .map(|seed| {
+            let mut kmeans = KMeans::new(8);
+            kmeans.seed = 20 + seed;
+            kmeans.fit(&x);
+            kmeans.inertia()
+        })
+        .collect();
+    let best = inertias.iter().cloned().fold(f32::INFINITY, f32::min);
+    assert!(inertias.iter().any(|i| *i > best));
+
+    let mut kmeans = KMeans::new(8);
+    kmeans.seed = 20;
+    kmeans.n_init = 10;
+    kmeans.fit(&x);
+    assert_eq!(kmeans.inertia(), best);
+    let pred = kmeans.predict(&x);
+    kmeans.n_threads = 4;
+    kmeans.fit(&x);
+    assert_eq!(kmeans.inertia(), best);
+    assert_eq!(kmeans.predict(&x), pred);
+}
+
+#[test]
+fn test_minibatch_kmeans() {
+    use clustering::{CentroidModel, KMeans, MiniBatchKMeans};
+    use rand::prelude::*;
+
+    // Three blobs of 100 rows streamed in shuffled batches of 20
+    let mut rng = StdRng::seed_from_u64(4);
+    let centres = [[0.0, 0.0], [10.0, 10.0], [-10.0, 10.0]];
+    let mut x: Vec<Vec<f32>> = (0..300)
+        .map(|i| centres[i % 3].iter().map(|c| c + rng.gen_range(-1.0..1.0)).collect())
+        .collect();
+    x.shuffle(&mut rng);
+
+    let mut streaming = MiniBatchKMeans::new(3);
+    for batch in x.chunks(20) {
+        streaming.partial_fit(batch);
+    }
+    let mut kmeans = KMeans::new(3);
+    kmeans.fit(&x);
+    let mut sampled = MiniBatchKMeans::new(3);
+    sampled.batch_size = 30;
+    sampled.max_iter = 20;
+    sampled.fit(&x);
+
+    // The fitted models are interchangeable, and agree up to the numbering of clusters
+    let models: Vec<&dyn CentroidModel> = vec![&kmeans, &streaming, &sampled];
+    let queries: Vec<Vec<f32>> = centres.iter().map(|c| c.to_vec()).collect();
+    for model in models {
+        let pred = model.predict(&x);
+        let ids = model.predict(&queries);
+        assert_eq!(
+            pred,
+            x.iter()
+                .map(|row| ids[if row[1] < 5. {
+                    0
+                } else if row[0] > 0. {
+                    1
+                } else {
+                    2
+                }])
+                .collect::<Vec<_>>()
+        );
+        for (centre, id) in centres.iter().zip(ids) {
+            assert!(model.cluster_centers()[id].iter().zip(centre).all(|(a, b)| (a - b).abs() < 0.3));
+        }
+    }
+}
+
+#[test]
+fn test_density_clustering() {
+    use clustering::{DBSCAN, HDBSCAN};
+    use rand::prelude::*;
+    use spatial::Algorithm;
+
+    // A dense blob of 40 rows around the origin, a sparse one of 40 rows around (20, 0), and
+    // three outliers at the end
+    let mut rng = StdRng::seed_from_u64(12);
+    let mut x: Vec<Vec<f32>> = (0..40).map(|_| vec![rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5)]).collect();
+    x.extend((0..40).map(|_| vec![20. + rng.gen_range(-4.0..4.0), rng.gen_range(-4.0..4.0)]));
+    x.extend(vec![vec![10.0, 15.0], vec![-12.0, -12.0], vec![10.0, -15.0]]);
+    let expected: Vec<Option<usize>> = (0..83)
+        .map(|i| {
+            if i < 40 {
+                Some(0)
+            } else if i < 80 {
+                Some(1)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let mut dbscan = DBSCAN::new(0.5, 4);
+    dbscan.fit(&x);
+    assert_eq!(&dbscan.labels()[..40], &expected[..40]);
+    assert!(dbscan.labels()[40..].iter().filter(|l| l.is_none()).count() > 30);
+
+    // A radius wide enough for the sparse blob finds both, all indices agreeing
+    dbscan.eps = 3.;
+    dbscan.fit(&x);
+    assert_eq!(dbscan.labels(), &expected[..]);
+    assert!(dbscan.core_samples().len() >= 75 && dbscan.core_samples().iter().all(|i| *i < 80));
+    for algorithm in [Algorithm::KdTree, Algorithm::BallTree] {
+        let mut indexed = DBSCAN::new(3., 4);
+        indexed.algorithm = algorithm;
+        indexed.fit(&x);
+        assert_eq!(indexed.labels(), dbscan.labels());
+        assert_eq!(indexed.core_samples(), dbscan.core_samples());
+    }
+
+    // On an integer grid neighbours sit exactly at eps, which every index must still count
+    for seed in 0..50 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let grid: Vec<Vec<f32>> = (0..200)
+            .map(|_| vec![rng.gen_range(0..20) as f32, rng.gen_range(0..20) as f32])
+            .collect();
+        let mut brute = DBSCAN::with_distance(1., 3, distance::Manhattan);
+        brute.fit(&grid);
+        for algorithm in [Algorithm::KdTree, Algorithm::BallTree] {
+            let mut indexed = DBSCAN::with_distance(1., 3, distance::Manhattan);
+            indexed.algorithm = algorithm;
+            indexed.fit(&grid);
+            assert_eq!(indexed.labels(), brute.labels());
+        }
+    }
+
+    // HDBSCAN needs no radius, whatever index it uses
+    for algorithm in [Algorithm::BruteForce, Algorithm::KdTree, Algorithm::BallTree] {
+        let mut hdbscan = HDBSCAN::new(10);
+        hdbscan.algorithm = algorithm;
+        hdbscan.fit(&x);
+        assert_eq!(hdbscan.labels(), &expected[..]);
+    }
+
+    // Clusters smaller than min_cluster_size are noise
+    let mut hdbscan = HDBSCAN::new(50);
+    hdbscan.fit(&x);
+    assert!(hdbscan.labels().iter().all(|l| l.is_none()));
+}
+
+#[test]
+fn test_agglomerative_clustering() {
+    use clustering::{AgglomerativeClustering, Linkage};
+
+    let x: Vec<Vec<f32>> = vec![vec![0.0], vec![1.0], vec![3.0], vec![7.0], vec![8.5]];
+    let expected = [
+        (Linkage::Single, [2.0, 4.0]),
+        (Linkage::Complete, [3.0, 8.5]),
+        (Linkage::Average, [2.5, 38.5 / 6.]),
+        (Linkage::Ward, [(4.0f32 / 3.).sqrt() * 2.5, 2.4f32.sqrt() * (7.75 - 4. / 3.)]),
+    ];
+    for (linkage, [third, fourth]) in expected.iter() {
+        let mut model = AgglomerativeClustering::new(*linkage);
+        model.fit(&x);
+        let matrix = model.linkage_matrix();
+        assert_eq!(matrix[..2], [[0.0, 1.0, 1.0, 2.0], [3.0, 4.0, 1.5, 2.0]]);
+        assert_eq!([matrix[2][0], matrix[2][1], matrix[2][3]], [2.0, 5.0, 3.0]);
+        assert_eq!([matrix[3][0], matrix[3][1], matrix[3][3]], [6.0, 7.0, 5.0]);
+        assert!((matrix[2][2] - third).abs() < 1e-4 && (matrix[3][2] - fourth).abs() < 1e-4);
+
+        assert_eq!(model.cut(1), vec![0; 5]);
+        assert_eq!(model.cut(2), vec![0, 0, 0, 1, 1]);
+        assert_eq!(model.cut(3), vec![0, 0, 1, 2, 2]);
+        assert_eq!(model.cut(9), vec![0, 1, 2, 3, 4]);
+        assert_eq!(model.cut_at_distance(1.2), vec![0, 0, 1, 2, 3]);
+        assert_eq!(model.cut_at_distance(fourth - 0.1), vec![0, 0, 0, 1, 1]);
+    }
+
+    // Cutting a larger tree recovers three well separated groups
+    let x: Vec<Vec<f32>> = (0..60)
+        .map(|i| vec![(i % 3) as f32 * 10. + (i / 3) as f32 * 0.1, (i / 3) as f32 * 0.05])
+        .collect();
+    let mut model = AgglomerativeClustering::new(Linkage::Ward);
+    model.fit(&x);
+    assert_eq!(model.linkage_matrix().len(), 59);
+    assert_eq!(model.cut(3), (0..60).map(|i| i % 3).collect::<Vec<_>>());
+    model.linkage = Linkage::Single;
+    model.fit(&x);
+    assert_eq!(model.cut_at_distance(5.), (0..60).map(|i| i % 3).collect::<Vec<_>>());
 }
 
 #[test]
@@ -5,6 +5,20 @@ pub fn factorial(n: u128) -> u128 {
     }
 }
 
+/// Logistic function, squashing any real number into (0, 1).
+pub fn sigmoid(x: f32) -> f32 {
+    1. / (1. + std::f32::consts::E.powf(-x))
+}
+
+/// Normalises scores such as log-likelihoods into probabilities, shifting by the maximum to avoid
+/// overflow.
+pub fn softmax(scores: &[f32]) -> Vec<f32> {
+    let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = scores.iter().map(|s| (s - max).exp()).collect();
+    let total: f32 = exps.iter().sum();
+    exps.iter().map(|e| e / total).collect()
+}
+
 #[allow(non_snake_case, unused_assignments, non_upper_case_globals)]
 pub fn Q_rsqrt(number: f32) -> f32 {
     // Fast inverse square root: https://en.wikipedia.org/wiki/Fast_inverse_square_root
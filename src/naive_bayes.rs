@@ -0,0 +1,275 @@
+use crate::math::softmax;
+use crate::validation::{check_fit, encode_labels};
+use std::hash::Hash;
+
+/// Gaussian Naive Bayes for continuous features, modelling every feature per class as a normal
+/// distribution.
+///
+/// `var_smoothing` times the largest feature variance is added to every variance, keeping
+/// constant features from producing zero variances.
+pub struct GaussianNB<L> {
+    pub var_smoothing: f32,
+    classes: Vec<L>,
+    log_priors: Vec<f32>,
+    means: Vec<Vec<f32>>,
+    variances: Vec<Vec<f32>>,
+}
+
+impl<L: Eq + Hash + Clone> GaussianNB<L> {
+    pub fn new() -> GaussianNB<L> {
+        GaussianNB {
+            var_smoothing: 1e-9,
+            classes: Vec::new(),
+            log_priors: Vec::new(),
+            means: Vec::new(),
+            variances: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], labels: &[L]) {
+        let (classes, class_ids, n_features) = check_labels(features, labels);
+        let rows = group_rows(features, &class_ids, classes.len());
+
+        self.means = rows.iter().map(|rows| mean(rows, n_features)).collect();
+        self.variances = rows
+            .iter()
+            .zip(self.means.iter())
+            .map(|(rows, means)| {
+                (0..n_features)
+                    .map(|f| rows.iter().map(|row| (row[f] - means[f]).powi(2)).sum::<f32>() / rows.len() as f32)
+                    .collect()
+            })
+            .collect();
+
+        let all_rows: Vec<&Vec<f32>> = features.iter().collect();
+        let overall_mean = mean(&all_rows, n_features);
+        let max_variance = (0..n_features)
+            .map(|f| features.iter().map(|row| (row[f] - overall_mean[f]).powi(2)).sum::<f32>() / features.len() as f32)
+            .fold(0., f32::max);
+        let epsilon = (self.var_smoothing * max_variance).max(f32::MIN_POSITIVE);
+        self.variances.iter_mut().flatten().for_each(|v| *v += epsilon);
+
+        self.log_priors = log_priors(&rows, features.len());
+        self.classes = classes;
+    }
+
+    /// Distinct training labels in order of first appearance, the columns of `predict_proba`.
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// Predicts a label per query, `None` when the model has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<L>> {
+        queries
+            .iter()
+            .map(|query| most_likely(&self.classes, &self.joint_log_likelihood(query)))
+            .collect()
+    }
+
+    /// Posterior probability of every class per query, ordered as `classes`.
+    pub fn predict_proba(&self, queries: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        queries.iter().map(|query| softmax(&self.joint_log_likelihood(query))).collect()
+    }
+
+    fn joint_log_likelihood(&self, query: &[f32]) -> Vec<f32> {
+        (0..self.classes.len())
+            .map(|c| {
+                self.log_priors[c]
+                    + query
+                        .iter()
+                        .zip(self.means[c].iter().zip(self.variances[c].iter()))
+                        .map(|(x, (mean, variance))| {
+                            -0.5 * (2. * std::f32::consts::PI * variance).ln() - (x - mean).powi(2) / (2. * variance)
+                        })
+                        .sum::<f32>()
+            })
+            .collect()
+    }
+}
+
+impl<L: Eq + Hash + Clone> Default for GaussianNB<L> {
+    fn default() -> GaussianNB<L> {
+        GaussianNB::new()
+    }
+}
+
+/// Multinomial Naive Bayes for count features, such as product quantities per basket or word
+/// counts per document.
+///
+/// Counts are Laplace smoothed by adding `alpha` to every feature of every class.
+pub struct MultinomialNB<L> {
+    pub alpha: f32,
+    classes: Vec<L>,
+    log_priors: Vec<f32>,
+    feature_log_probs: Vec<Vec<f32>>,
+}
+
+impl<L: Eq + Hash + Clone> MultinomialNB<L> {
+    pub fn new() -> MultinomialNB<L> {
+        MultinomialNB {
+            alpha: 1.,
+            classes: Vec::new(),
+            log_priors: Vec::new(),
+            feature_log_probs: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], labels: &[L]) {
+        let (classes, class_ids, n_features) = check_labels(features, labels);
+        let rows = group_rows(features, &class_ids, classes.len());
+
+        self.feature_log_probs = rows
+            .iter()
+            .map(|rows| {
+                let counts: Vec<f32> = (0..n_features)
+                    .map(|f| rows.iter().map(|row| row[f]).sum::<f32>() + self.alpha)
+                    .collect();
+                let total: f32 = counts.iter().sum();
+                counts.iter().map(|count| (count / total).ln()).collect()
+            })
+            .collect();
+        self.log_priors = log_priors(&rows, features.len());
+        self.classes = classes;
+    }
+
+    /// Distinct training labels in order of first appearance, the columns of `predict_proba`.
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// Predicts a label per query, `None` when the model has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<L>> {
+        queries
+            .iter()
+            .map(|query| most_likely(&self.classes, &self.joint_log_likelihood(query)))
+            .collect()
+    }
+
+    /// Posterior probability of every class per query, ordered as `classes`.
+    pub fn predict_proba(&self, queries: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        queries.iter().map(|query| softmax(&self.joint_log_likelihood(query))).collect()
+    }
+
+    fn joint_log_likelihood(&self, query: &[f32]) -> Vec<f32> {
+        (0..self.classes.len())
+            .map(|c| self.log_priors[c] + query.iter().zip(self.feature_log_probs[c].iter()).map(|(x, p)| x * p).sum::<f32>())
+            .collect()
+    }
+}
+
+impl<L: Eq + Hash + Clone> Default for MultinomialNB<L> {
+    fn default() -> MultinomialNB<L> {
+        MultinomialNB::new()
+    }
+}
+
+/// Bernoulli Naive Bayes for binary features, such as whether a basket contains a product.
+///
+/// Features above `binarize` count as present. Presence counts are Laplace smoothed by adding
+/// `alpha`, so a feature never seen with a class does not rule that class out.
+pub struct BernoulliNB<L> {
+    pub alpha: f32,
+    pub binarize: f32,
+    classes: Vec<L>,
+    log_priors: Vec<f32>,
+    feature_probs: Vec<Vec<f32>>,
+}
+
+impl<L: Eq + Hash + Clone> BernoulliNB<L> {
+    pub fn new() -> BernoulliNB<L> {
+        BernoulliNB {
+            alpha: 1.,
+            binarize: 0.,
+            classes: Vec::new(),
+            log_priors: Vec::new(),
+            feature_probs: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], labels: &[L]) {
+        let (classes, class_ids, n_features) = check_labels(features, labels);
+        let rows = group_rows(features, &class_ids, classes.len());
+
+        self.feature_probs = rows
+            .iter()
+            .map(|rows| {
+                (0..n_features)
+                    .map(|f| {
+                        let present = rows.iter().filter(|row| row[f] > self.binarize).count() as f32;
+                        (present + self.alpha) / (rows.len() as f32 + 2. * self.alpha)
+                    })
+                    .collect()
+            })
+            .collect();
+        self.log_priors = log_priors(&rows, features.len());
+        self.classes = classes;
+    }
+
+    /// Distinct training labels in order of first appearance, the columns of `predict_proba`.
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// Predicts a label per query, `None` when the model has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<L>> {
+        queries
+            .iter()
+            .map(|query| most_likely(&self.classes, &self.joint_log_likelihood(query)))
+            .collect()
+    }
+
+    /// Posterior probability of every class per query, ordered as `classes`.
+    pub fn predict_proba(&self, queries: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        queries.iter().map(|query| softmax(&self.joint_log_likelihood(query))).collect()
+    }
+
+    fn joint_log_likelihood(&self, query: &[f32]) -> Vec<f32> {
+        (0..self.classes.len())
+            .map(|c| {
+                self.log_priors[c]
+                    + query
+                        .iter()
+                        .zip(self.feature_probs[c].iter())
+                        .map(|(x, p)| if *x > self.binarize { p.ln() } else { (1. - p).ln() })
+                        .sum::<f32>()
+            })
+            .collect()
+    }
+}
+
+impl<L: Eq + Hash + Clone> Default for BernoulliNB<L> {
+    fn default() -> BernoulliNB<L> {
+        BernoulliNB::new()
+    }
+}
+
+// Validates the training data, returning the encoded labels and the number of features.
+fn check_labels<L: Eq + Hash + Clone>(features: &[Vec<f32>], labels: &[L]) -> (Vec<L>, Vec<usize>, usize) {
+    check_fit(features, labels.len());
+    let (classes, class_ids) = encode_labels(labels);
+    (classes, class_ids, features.first().map_or(0, |row| row.len()))
+}
+
+fn group_rows<'a>(features: &'a [Vec<f32>], class_ids: &[usize], n_classes: usize) -> Vec<Vec<&'a Vec<f32>>> {
+    let mut rows = vec![Vec::new(); n_classes];
+    for (row, c) in features.iter().zip(class_ids) {
+        rows[*c].push(row);
+    }
+    rows
+}
+
+fn mean(rows: &[&Vec<f32>], n_features: usize) -> Vec<f32> {
+    (0..n_features)
+        .map(|f| rows.iter().map(|row| row[f]).sum::<f32>() / rows.len() as f32)
+        .collect()
+}
+
+fn log_priors(rows: &[Vec<&Vec<f32>>], n_samples: usize) -> Vec<f32> {
+    rows.iter().map(|rows| (rows.len() as f32 / n_samples as f32).ln()).collect()
+}
+
+fn most_likely<L: Clone>(classes: &[L], log_likelihoods: &[f32]) -> Option<L> {
+    (0..classes.len())
+        .max_by(|&a, &b| log_likelihoods[a].total_cmp(&log_likelihoods[b]).then(b.cmp(&a)))
+        .map(|c| classes[c].clone())
+}
@@ -38,7 +38,7 @@ impl Node {
     }
 
     pub fn sigmoid(&self, weighted_sum: &f32) -> f32 {
-        1. / (1. + std::f32::consts::E.powf(-weighted_sum))
+        crate::math::sigmoid(*weighted_sum)
     }
 
     pub fn d_sigmoid(&self, output: &f32) -> f32 {
@@ -0,0 +1,24 @@
+/// Runs `task` for every index in `0..n` and returns the results in index order.
+///
+/// Indices are dealt round-robin over `n_threads` threads, capped at `n` so that no thread is left
+/// without work; a value of 0 or 1 runs every task on the calling thread.
+pub(crate) fn map_indices<T, F>(n: usize, n_threads: usize, task: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let n_threads = n_threads.min(n);
+    if n_threads <= 1 {
+        return (0..n).map(task).collect();
+    }
+
+    let task = &task;
+    let mut results: Vec<(usize, T)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..n_threads)
+            .map(|thread| scope.spawn(move || (thread..n).step_by(n_threads).map(|i| (i, task(i))).collect::<Vec<_>>()))
+            .collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
@@ -1,3 +1,8 @@
+use crate::math::{sigmoid, softmax};
+use crate::validation::{check_fit, encode_labels};
+use rand::prelude::*;
+use std::hash::Hash;
+
 /// Linear regression on one variable.
 pub fn linear_regression(x_vec: &[f32], y: &[f32]) -> impl Fn(f32) -> f32 {
     assert_eq!(x_vec.len(), y.len());
@@ -22,3 +27,162 @@ pub fn linear_regression(x_vec: &[f32], y: &[f32]) -> impl Fn(f32) -> f32 {
 
     move |x: f32| a + b * x
 }
+
+/// Regularisation added to the cross-entropy loss of a [`LogisticRegression`], scaled by its
+/// strength. Intercepts are never penalised.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Penalty {
+    None,
+    /// Lasso, applied as a soft-thresholding step after every update so coefficients can reach
+    /// exactly zero.
+    L1(f32),
+    /// Ridge, shrinking coefficients towards zero.
+    L2(f32),
+}
+
+/// Logistic regression classifier trained by gradient descent on the cross-entropy loss.
+///
+/// Two classes are modelled by a single sigmoid, more classes by a softmax with a row of
+/// coefficients per class. Every epoch takes one step on the whole training set, or one step per
+/// mini-batch of `batch_size` rows drawn in an order shuffled by `seed`.
+pub struct LogisticRegression<L> {
+    pub learning_rate: f32,
+    pub epochs: usize,
+    pub batch_size: Option<usize>,
+    pub penalty: Penalty,
+    pub seed: u64,
+    classes: Vec<L>,
+    coefficients: Vec<Vec<f32>>,
+    intercepts: Vec<f32>,
+}
+
+impl<L: Eq + Hash + Clone> LogisticRegression<L> {
+    pub fn new() -> LogisticRegression<L> {
+        LogisticRegression {
+            learning_rate: 0.1,
+            epochs: 1000,
+            batch_size: None,
+            penalty: Penalty::None,
+            seed: 0,
+            classes: Vec::new(),
+            coefficients: Vec::new(),
+            intercepts: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], labels: &[L]) {
+        check_fit(features, labels.len());
+        let n_features = features.first().map_or(0, |row| row.len());
+
+        let (classes, class_ids) = encode_labels(labels);
+        let n_outputs = if classes.len() == 2 { 1 } else { classes.len() };
+        self.coefficients = vec![vec![0.; n_features]; n_outputs];
+        self.intercepts = vec![0.; n_outputs];
+        self.classes = classes;
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut order: Vec<usize> = (0..features.len()).collect();
+        let batch_size = self.batch_size.unwrap_or(order.len()).max(1);
+        for _ in 0..self.epochs {
+            if self.batch_size.is_some() {
+                order.shuffle(&mut rng);
+            }
+            for batch in order.chunks(batch_size) {
+                self.step(features, &class_ids, batch);
+            }
+        }
+    }
+
+    // One gradient descent step on the mean loss of the rows in `batch`.
+    fn step(&mut self, features: &[Vec<f32>], class_ids: &[usize], batch: &[usize]) {
+        let n_outputs = self.intercepts.len();
+        let mut coefficient_gradients = vec![vec![0.; features[0].len()]; n_outputs];
+        let mut intercept_gradients = vec![0.; n_outputs];
+        for &row in batch {
+            let proba = self.proba(&features[row]);
+            // Predicted minus observed probability, for the positive class when there are two
+            let errors: Vec<f32> = if self.classes.len() == 2 {
+                vec![proba[1] - (class_ids[row] == 1) as u8 as f32]
+            } else {
+                (0..n_outputs).map(|c| proba[c] - (class_ids[row] == c) as u8 as f32).collect()
+            };
+            for (c, error) in errors.iter().enumerate() {
+                intercept_gradients[c] += error / batch.len() as f32;
+                for (gradient, x) in coefficient_gradients[c].iter_mut().zip(features[row].iter()) {
+                    *gradient += error * x / batch.len() as f32;
+                }
+            }
+        }
+
+        let learning_rate = self.learning_rate;
+        for (c, gradients) in coefficient_gradients.iter().enumerate() {
+            self.intercepts[c] -= learning_rate * intercept_gradients[c];
+            for (coefficient, gradient) in self.coefficients[c].iter_mut().zip(gradients.iter()) {
+                *coefficient = match self.penalty {
+                    Penalty::None => *coefficient - learning_rate * gradient,
+                    Penalty::L2(strength) => *coefficient - learning_rate * (gradient + strength * *coefficient),
+                    Penalty::L1(strength) => {
+                        let stepped = *coefficient - learning_rate * gradient;
+                        stepped.signum() * (stepped.abs() - learning_rate * strength).max(0.)
+                    }
+                };
+            }
+        }
+    }
+
+    /// Distinct training labels in order of first appearance, the columns of `predict_proba`.
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// Learned coefficients, a single row for the second class when there are two classes and a
+    /// row per class otherwise.
+    pub fn coefficients(&self) -> &[Vec<f32>] {
+        &self.coefficients
+    }
+
+    /// Learned intercepts, one per row of `coefficients`.
+    pub fn intercepts(&self) -> &[f32] {
+        &self.intercepts
+    }
+
+    /// Predicts a label per query, `None` when the model has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<L>> {
+        self.predict_proba(queries)
+            .iter()
+            .map(|proba| {
+                (0..proba.len())
+                    .max_by(|&a, &b| proba[a].total_cmp(&proba[b]).then(b.cmp(&a)))
+                    .map(|c| self.classes[c].clone())
+            })
+            .collect()
+    }
+
+    /// Probability of every class per query, ordered as `classes`.
+    pub fn predict_proba(&self, queries: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        queries.iter().map(|query| self.proba(query)).collect()
+    }
+
+    fn proba(&self, query: &[f32]) -> Vec<f32> {
+        let scores: Vec<f32> = self
+            .coefficients
+            .iter()
+            .zip(self.intercepts.iter())
+            .map(|(coefficients, intercept)| coefficients.iter().zip(query.iter()).map(|(w, x)| w * x).sum::<f32>() + intercept)
+            .collect();
+        if self.classes.len() == 2 {
+            let p = sigmoid(scores[0]);
+            vec![1. - p, p]
+        } else if scores.is_empty() {
+            Vec::new()
+        } else {
+            softmax(&scores)
+        }
+    }
+}
+
+impl<L: Eq + Hash + Clone> Default for LogisticRegression<L> {
+    fn default() -> LogisticRegression<L> {
+        LogisticRegression::new()
+    }
+}
@@ -0,0 +1,372 @@
+use crate::distance::Distance;
+
+/// Nearest-neighbour queries over a fixed set of points.
+///
+/// Results are `(index, distance)` pairs sorted by distance, ties by index, so every index returns
+/// the same neighbours for the same query.
+pub trait SpatialIndex {
+    /// The `k` points closest to `query`.
+    fn k_nearest(&self, query: &[f32], k: usize) -> Vec<(usize, f32)>;
+
+    /// Every point at most `radius` away from `query`.
+    fn within_radius(&self, query: &[f32], radius: f32) -> Vec<(usize, f32)>;
+}
+
+/// Which index answers neighbour queries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    BruteForce,
+    KdTree,
+    BallTree,
+}
+
+/// Points stored in a flat list, every query computing the distance to all of them.
+pub struct BruteForce<D> {
+    points: Vec<Vec<f32>>,
+    distance: D,
+}
+
+impl<D: Distance> BruteForce<D> {
+    pub fn new(points: &[Vec<f32>], distance: D) -> BruteForce<D> {
+        BruteForce {
+            points: points.to_vec(),
+            distance,
+        }
+    }
+}
+
+impl<D: Distance> SpatialIndex for BruteForce<D> {
+    fn k_nearest(&self, query: &[f32], k: usize) -> Vec<(usize, f32)> {
+        let mut neighbours: Vec<(usize, f32)> = self.points.iter().map(|p| self.distance.distance(p, query)).enumerate().collect();
+        neighbours.sort_by(compare);
+        neighbours.truncate(k);
+        neighbours
+    }
+
+    fn within_radius(&self, query: &[f32], radius: f32) -> Vec<(usize, f32)> {
+        let mut neighbours: Vec<(usize, f32)> = self
+            .points
+            .iter()
+            .map(|p| self.distance.distance(p, query))
+            .enumerate()
+            .filter(|(_, d)| *d <= radius)
+            .collect();
+        neighbours.sort_by(compare);
+        neighbours
+    }
+}
+
+const LEAF_SIZE: usize = 16;
+
+enum KdNode {
+    Leaf(Vec<usize>),
+    // Points in `left` are at most `value` along `axis`, points in `right` at least `value`
+    Split {
+        axis: usize,
+        value: f32,
+        left: usize,
+        right: usize,
+    },
+}
+
+/// A k-d tree, splitting the points at the median of their widest feature.
+///
+/// Branches are pruned with [`Distance::axis_bound`]; distances without that bound are answered
+/// correctly but visit every leaf.
+pub struct KdTree<D> {
+    points: Vec<Vec<f32>>,
+    distance: D,
+    nodes: Vec<KdNode>,
+}
+
+impl<D: Distance> KdTree<D> {
+    pub fn new(points: &[Vec<f32>], distance: D) -> KdTree<D> {
+        let mut tree = KdTree {
+            points: points.to_vec(),
+            distance,
+            nodes: Vec::new(),
+        };
+        tree.build((0..points.len()).collect());
+        tree
+    }
+
+    fn build(&mut self, mut indices: Vec<usize>) -> usize {
+        let n_features = self.points.first().map_or(0, |p| p.len());
+        let spread = |axis: usize| {
+            let values = indices.iter().map(|&i| self.points[i][axis]);
+            values.clone().fold(f32::MIN, f32::max) - values.fold(f32::MAX, f32::min)
+        };
+        let axis = (0..n_features).max_by(|&a, &b| spread(a).total_cmp(&spread(b)));
+
+        let axis = match axis {
+            Some(axis) if indices.len() > LEAF_SIZE && spread(axis) > 0. => axis,
+            _ => {
+                self.nodes.push(KdNode::Leaf(indices));
+                return self.nodes.len() - 1;
+            }
+        };
+
+        indices.sort_by(|&a, &b| self.points[a][axis].total_cmp(&self.points[b][axis]));
+        let right_indices = indices.split_off(indices.len() / 2);
+        let value = self.points[right_indices[0]][axis];
+
+        let node = self.nodes.len();
+        self.nodes.push(KdNode::Leaf(Vec::new())); // Replaced once the children exist
+        let left = self.build(indices);
+        let right = self.build(right_indices);
+        self.nodes[node] = KdNode::Split { axis, value, left, right };
+        node
+    }
+
+    fn search(&self, node: usize, query: &[f32], search: &mut Search) {
+        match &self.nodes[node] {
+            KdNode::Leaf(indices) => {
+                for &i in indices.iter() {
+                    search.visit(i, self.distance.distance(&self.points[i], query));
+                }
+            }
+            KdNode::Split { axis, value, left, right } => {
+                let diff = query[*axis] - value;
+                let (near, far) = if diff < 0. { (*left, *right) } else { (*right, *left) };
+                self.search(near, query, search);
+                match self.distance.axis_bound(diff.abs()) {
+                    Some(lower) if lower > search.bound() => {}
+                    _ => self.search(far, query, search),
+                }
+            }
+        }
+    }
+}
+
+impl<D: Distance> SpatialIndex for KdTree<D> {
+    fn k_nearest(&self, query: &[f32], k: usize) -> Vec<(usize, f32)> {
+        let mut search = Search::Nearest(k, Vec::with_capacity(k + 1));
+        if !self.points.is_empty() && k > 0 {
+            self.search(0, query, &mut search);
+        }
+        search.into_neighbours()
+    }
+
+    fn within_radius(&self, query: &[f32], radius: f32) -> Vec<(usize, f32)> {
+        let mut search = Search::Radius(radius, Vec::new());
+        if !self.points.is_empty() {
+            self.search(0, query, &mut search);
+        }
+        search.into_neighbours()
+    }
+}
+
+struct BallNode {
+    center: Vec<f32>,
+    radius: f32,
+    children: Option<(usize, usize)>,
+    indices: Vec<usize>,
+}
+
+/// A ball tree, nesting every point in hyperspheres around the centroids of its subtrees.
+///
+/// Branches are pruned with the triangle inequality when [`Distance::is_metric`] holds; other
+/// distances are answered correctly but visit every leaf.
+pub struct BallTree<D> {
+    points: Vec<Vec<f32>>,
+    distance: D,
+    nodes: Vec<BallNode>,
+}
+
+impl<D: Distance> BallTree<D> {
+    pub fn new(points: &[Vec<f32>], distance: D) -> BallTree<D> {
+        let mut tree = BallTree {
+            points: points.to_vec(),
+            distance,
+            nodes: Vec::new(),
+        };
+        tree.build((0..points.len()).collect());
+        tree
+    }
+
+    fn build(&mut self, indices: Vec<usize>) -> usize {
+        let n_features = self.points.first().map_or(0, |p| p.len());
+        let center: Vec<f32> = (0..n_features)
+            .map(|f| indices.iter().map(|&i| self.points[i][f]).sum::<f32>() / indices.len() as f32)
+            .collect();
+        let distances: Vec<f32> = indices.iter().map(|&i| self.distance.distance(&self.points[i], &center)).collect();
+        let radius = distances.iter().cloned().fold(0., f32::max);
+
+        let node = self.nodes.len();
+        self.nodes.push(BallNode {
+            center,
+            radius,
+            children: None,
+            indices: Vec::new(),
+        });
+        if indices.len() <= LEAF_SIZE || radius == 0. {
+            self.nodes[node].indices = indices;
+            return node;
+        }
+
+        // Split around two far apart pivots: the point farthest from the center, and the point
+        // farthest from that one
+        let farthest_from = |from: &[f32]| -> usize {
+            *indices
+                .iter()
+                .max_by(|&&a, &&b| {
+                    self.distance
+                        .distance(&self.points[a], from)
+                        .total_cmp(&self.distance.distance(&self.points[b], from))
+                })
+                .unwrap()
+        };
+        let a = farthest_from(&self.nodes[node].center);
+        let b = farthest_from(&self.points[a]);
+        let (mut left, mut right): (Vec<usize>, Vec<usize>) = indices.iter().partition(|&&i| {
+            self.distance.distance(&self.points[i], &self.points[a]) <= self.distance.distance(&self.points[i], &self.points[b])
+        });
+        if left.is_empty() || right.is_empty() {
+            left = indices;
+            right = left.split_off(left.len() / 2);
+        }
+
+        let left = self.build(left);
+        let right = self.build(right);
+        self.nodes[node].children = Some((left, right));
+        node
+    }
+
+    // Smallest distance any point inside the node's ball can have to `query`, up to rounding: the
+    // centre and radius are rounded, so points on the boundary may be slightly closer.
+    fn lower_bound(&self, node: usize, query: &[f32]) -> f32 {
+        if self.distance.is_metric() {
+            (self.distance.distance(query, &self.nodes[node].center) - self.nodes[node].radius).max(0.)
+        } else {
+            0.
+        }
+    }
+
+    fn search(&self, node: usize, query: &[f32], search: &mut Search) {
+        // Only prune with some slack, so that points exactly at the bound are still visited
+        let bound = search.bound();
+        if self.lower_bound(node, query) > bound + bound.abs() * 1e-5 + 1e-6 {
+            return;
+        }
+        match self.nodes[node].children {
+            None => {
+                for &i in self.nodes[node].indices.iter() {
+                    search.visit(i, self.distance.distance(&self.points[i], query));
+                }
+            }
+            Some((left, right)) => {
+                // Descend into the closer ball first to tighten the bound early
+                let (near, far) = if self.lower_bound(left, query) <= self.lower_bound(right, query) {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                self.search(near, query, search);
+                self.search(far, query, search);
+            }
+        }
+    }
+}
+
+impl<D: Distance> SpatialIndex for BallTree<D> {
+    fn k_nearest(&self, query: &[f32], k: usize) -> Vec<(usize, f32)> {
+        let mut search = Search::Nearest(k, Vec::with_capacity(k + 1));
+        if !self.points.is_empty() && k > 0 {
+            self.search(0, query, &mut search);
+        }
+        search.into_neighbours()
+    }
+
+    fn within_radius(&self, query: &[f32], radius: f32) -> Vec<(usize, f32)> {
+        let mut search = Search::Radius(radius, Vec::new());
+        if !self.points.is_empty() {
+            self.search(0, query, &mut search);
+        }
+        search.into_neighbours()
+    }
+}
+
+/// Any of the indices, chosen at runtime by [`Algorithm`].
+pub enum NeighbourIndex<D> {
+    BruteForce(BruteForce<D>),
+    KdTree(KdTree<D>),
+    BallTree(BallTree<D>),
+}
+
+impl<D: Distance> NeighbourIndex<D> {
+    pub fn build(algorithm: Algorithm, points: &[Vec<f32>], distance: D) -> NeighbourIndex<D> {
+        match algorithm {
+            Algorithm::BruteForce => NeighbourIndex::BruteForce(BruteForce::new(points, distance)),
+            Algorithm::KdTree => NeighbourIndex::KdTree(KdTree::new(points, distance)),
+            Algorithm::BallTree => NeighbourIndex::BallTree(BallTree::new(points, distance)),
+        }
+    }
+}
+
+impl<D: Distance> SpatialIndex for NeighbourIndex<D> {
+    fn k_nearest(&self, query: &[f32], k: usize) -> Vec<(usize, f32)> {
+        match self {
+            NeighbourIndex::BruteForce(index) => index.k_nearest(query, k),
+            NeighbourIndex::KdTree(index) => index.k_nearest(query, k),
+            NeighbourIndex::BallTree(index) => index.k_nearest(query, k),
+        }
+    }
+
+    fn within_radius(&self, query: &[f32], radius: f32) -> Vec<(usize, f32)> {
+        match self {
+            NeighbourIndex::BruteForce(index) => index.within_radius(query, radius),
+            NeighbourIndex::KdTree(index) => index.within_radius(query, radius),
+            NeighbourIndex::BallTree(index) => index.within_radius(query, radius),
+        }
+    }
+}
+
+fn compare(a: &(usize, f32), b: &(usize, f32)) -> std::cmp::Ordering {
+    a.1.total_cmp(&b.1).then(a.0.cmp(&b.0))
+}
+
+// Neighbours collected during a tree traversal.
+enum Search {
+    // The k nearest so far, kept sorted
+    Nearest(usize, Vec<(usize, f32)>),
+    Radius(f32, Vec<(usize, f32)>),
+}
+
+impl Search {
+    // Distance beyond which no point can be collected anymore.
+    fn bound(&self) -> f32 {
+        match self {
+            Search::Nearest(k, nearest) if nearest.len() == *k => nearest[k - 1].1,
+            Search::Nearest(..) => f32::INFINITY,
+            Search::Radius(radius, _) => *radius,
+        }
+    }
+
+    fn visit(&mut self, index: usize, distance: f32) {
+        match self {
+            Search::Nearest(k, nearest) => {
+                let candidate = (index, distance);
+                let position = nearest.partition_point(|n| compare(n, &candidate) == std::cmp::Ordering::Less);
+                if position < *k {
+                    nearest.insert(position, candidate);
+                    nearest.truncate(*k);
+                }
+            }
+            Search::Radius(radius, neighbours) => {
+                if distance <= *radius {
+                    neighbours.push((index, distance));
+                }
+            }
+        }
+    }
+
+    fn into_neighbours(self) -> Vec<(usize, f32)> {
+        match self {
+            Search::Nearest(_, nearest) => nearest,
+            Search::Radius(_, mut neighbours) => {
+                neighbours.sort_by(compare);
+                neighbours
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::validation::{check_fit, encode_labels};
+use std::hash::Hash;
+
+/// Similarity measure a support vector machine separates classes in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kernel {
+    Linear,
+    /// `(gamma * <a, b> + coef0) ^ degree`
+    Polynomial {
+        degree: i32,
+        gamma: f32,
+        coef0: f32,
+    },
+    /// `exp(-gamma * |a - b|^2)`
+    Rbf {
+        gamma: f32,
+    },
+}
+
+impl Kernel {
+    pub fn compute(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Kernel::Linear => dot(a, b),
+            Kernel::Polynomial { degree, gamma, coef0 } => (gamma * dot(a, b) + coef0).powi(*degree),
+            Kernel::Rbf { gamma } => (-gamma * a.iter().zip(b.iter()).map(|(x, y)| (x - y).powi(2)).sum::<f32>()).exp(),
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+/// Support vector classifier trained with sequential minimal optimisation.
+///
+/// Every step optimises the pair of dual variables that most violates the optimality conditions,
+/// until no pair violates them by more than `tol` or `max_iter` steps were taken. `c` bounds the
+/// dual variables, trading margin width against training errors. Two classes are separated by a
+/// single machine, more classes one-vs-rest with a machine per class.
+pub struct SVC<L> {
+    pub c: f32,
+    pub kernel: Kernel,
+    pub tol: f32,
+    pub max_iter: usize,
+    classes: Vec<L>,
+    support: Vec<usize>,
+    support_vectors: Vec<Vec<f32>>,
+    machines: Vec<Machine>,
+}
+
+// A binary machine: dual coefficients `alpha * y` per support vector, and the intercept.
+struct Machine {
+    dual_coefs: Vec<f32>,
+    intercept: f32,
+}
+
+impl<L: Eq + Hash + Clone> SVC<L> {
+    pub fn new(kernel: Kernel) -> SVC<L> {
+        SVC {
+            c: 1.,
+            kernel,
+            tol: 1e-3,
+            max_iter: 100_000,
+            classes: Vec::new(),
+            support: Vec::new(),
+            support_vectors: Vec::new(),
+            machines: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], labels: &[L]) {
+        check_fit(features, labels.len());
+        let (classes, class_ids) = encode_labels(labels);
+        let gram: Vec<Vec<f32>> = features
+            .iter()
+            .map(|a| features.iter().map(|b| self.kernel.compute(a, b)).collect())
+            .collect();
+        // The second class is the positive one of a binary problem
+        let positives: Vec<usize> = match classes.len() {
+            0 | 1 => Vec::new(),
+            2 => vec![1],
+            n => (0..n).collect(),
+        };
+        let solutions: Vec<(Vec<f32>, f32)> = positives
+            .iter()
+            .map(|&positive| {
+                let y: Vec<f32> = class_ids.iter().map(|&id| if id == positive { 1. } else { -1. }).collect();
+                self.smo(&gram, &y)
+            })
+            .collect();
+
+        // Rows with a non-zero dual variable in any machine
+        self.support = (0..features.len())
+            .filter(|&i| solutions.iter().any(|(alpha_y, _)| alpha_y[i] != 0.))
+            .collect();
+        self.support_vectors = self.support.iter().map(|&i| features[i].clone()).collect();
+        self.machines = solutions
+            .into_iter()
+            .map(|(alpha_y, intercept)| Machine {
+                dual_coefs: self.support.iter().map(|&i| alpha_y[i]).collect(),
+                intercept,
+            })
+            .collect();
+        self.classes = classes;
+    }
+
+    // Solves the dual problem for labels `y` of +1 and -1, returning `alpha * y` and the intercept.
+    fn smo(&self, gram: &[Vec<f32>], y: &[f32]) -> (Vec<f32>, f32) {
+        let n = y.len();
+        let mut alpha = vec![0.; n];
+        // Gradient of the dual objective, `Q alpha - 1` with `Q_ij = y_i y_j K_ij`
+        let mut gradient = vec![-1.; n];
+        let c = self.c;
+        // Whether a variable may grow, or shrink, along the direction that decreases the objective
+        let can_increase = |a: f32, y: f32| (y > 0. && a < c) || (y < 0. && a > 0.);
+        let can_decrease = |a: f32, y: f32| (y > 0. && a > 0.) || (y < 0. && a < c);
+
+        for _ in 0..self.max_iter {
+            let violation = |t: usize| -y[t] * gradient[t];
+            let i = (0..n)
+                .filter(|&t| can_increase(alpha[t], y[t]))
+                .max_by(|&a, &b| violation(a).total_cmp(&violation(b)));
+            let j = (0..n)
+                .filter(|&t| can_decrease(alpha[t], y[t]))
+                .min_by(|&a, &b| violation(a).total_cmp(&violation(b)));
+            let (i, j) = match (i, j) {
+                (Some(i), Some(j)) if -y[i] * gradient[i] + y[j] * gradient[j] > self.tol => (i, j),
+                _ => break,
+            };
+
+            let (old_i, old_j) = (alpha[i], alpha[j]);
+            let curvature = (gram[i][i] + gram[j][j] - 2. * gram[i][j]).max(1e-12);
+            if y[i] != y[j] {
+                let delta = (-gradient[i] - gradient[j]) / curvature;
+                let diff = alpha[i] - alpha[j];
+                alpha[i] += delta;
+                alpha[j] += delta;
+                if diff > 0. && alpha[j] < 0. {
+                    alpha[j] = 0.;
+                    alpha[i] = diff;
+                } else if diff <= 0. && alpha[i] < 0. {
+                    alpha[i] = 0.;
+                    alpha[j] = -diff;
+                }
+                if diff > 0. && alpha[i] > c {
+                    alpha[i] = c;
+                    alpha[j] = c - diff;
+                } else if diff <= 0. && alpha[j] > c {
+                    alpha[j] = c;
+                    alpha[i] = c + diff;
+                }
+            } else {
+                let delta = (gradient[i] - gradient[j]) / curvature;
+                let sum = alpha[i] + alpha[j];
+                alpha[i] -= delta;
+                alpha[j] += delta;
+                if sum > c && alpha[i] > c {
+                    alpha[i] = c;
+                    alpha[j] = sum - c;
+                } else if sum <= c && alpha[j] < 0. {
+                    alpha[j] = 0.;
+                    alpha[i] = sum;
+                }
+                if sum > c && alpha[j] > c {
+                    alpha[j] = c;
+                    alpha[i] = sum - c;
+                } else if sum <= c && alpha[i] < 0. {
+                    alpha[i] = 0.;
+                    alpha[j] = sum;
+                }
+            }
+
+            let (delta_i, delta_j) = (alpha[i] - old_i, alpha[j] - old_j);
+            for ((g, y_t), row) in gradient.iter_mut().zip(y.iter()).zip(gram.iter()) {
+                *g += y_t * (y[i] * row[i] * delta_i + y[j] * row[j] * delta_j);
+            }
+        }
+
+        // The intercept averages over free support vectors, which lie exactly on the margin
+        let (mut upper, mut lower, mut free_sum, mut n_free) = (f32::INFINITY, f32::NEG_INFINITY, 0., 0);
+        for ((&a, &y_t), g) in alpha.iter().zip(y.iter()).zip(gradient.iter()) {
+            let y_gradient = y_t * g;
+            if (a >= c && y_t < 0.) || (a <= 0. && y_t > 0.) {
+                upper = upper.min(y_gradient);
+            } else if a >= c || a <= 0. {
+                lower = lower.max(y_gradient);
+            } else {
+                free_sum += y_gradient;
+                n_free += 1;
+            }
+        }
+        let rho = if n_free > 0 {
+            free_sum / n_free as f32
+        } else {
+            (upper + lower) / 2.
+        };
+        (alpha.iter().zip(y.iter()).map(|(a, y)| a * y).collect(), -rho)
+    }
+
+    /// Distinct training labels in order of first appearance.
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// Indices of the training rows that ended up as support vectors.
+    pub fn support(&self) -> &[usize] {
+        &self.support
+    }
+
+    /// Training rows with a non-zero dual coefficient in any of the machines.
+    pub fn support_vectors(&self) -> &[Vec<f32>] {
+        &self.support_vectors
+    }
+
+    /// Signed distance-like score per query and machine: a single score for two classes, positive
+    /// for the second class, and a score per class otherwise.
+    pub fn decision_function(&self, queries: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        queries
+            .iter()
+            .map(|query| {
+                let kernels: Vec<f32> = self.support_vectors.iter().map(|sv| self.kernel.compute(sv, query)).collect();
+                self.machines
+                    .iter()
+                    .map(|machine| dot(&machine.dual_coefs, &kernels) + machine.intercept)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Predicts a label per query, `None` when the classifier has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<L>> {
+        if self.classes.len() == 1 {
+            return vec![Some(self.classes[0].clone()); queries.len()];
+        }
+        self.decision_function(queries)
+            .iter()
+            .map(|scores| {
+                let class = match scores.len() {
+                    0 => return None,
+                    1 => (scores[0] > 0.) as usize,
+                    _ => (0..scores.len())
+                        .max_by(|&a, &b| scores[a].total_cmp(&scores[b]).then(b.cmp(&a)))
+                        .unwrap(),
+                };
+                Some(self.classes[class].clone())
+            })
+            .collect()
+    }
+}
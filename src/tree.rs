@@ -0,0 +1,459 @@
+use crate::validation::{check_fit, encode_labels};
+use rand::prelude::*;
+use std::fmt::{Debug, Write};
+use std::hash::Hash;
+
+/// Impurity measure a classification tree minimises when choosing splits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Criterion {
+    Gini,
+    Entropy,
+}
+
+/// CART decision tree classifier.
+///
+/// Nodes are split on the feature and threshold that most reduce the weighted impurity of their
+/// children, until a node is pure, `max_depth` is reached or a split would leave a child with fewer
+/// than `min_samples_leaf` samples.
+pub struct DecisionTreeClassifier<L> {
+    pub criterion: Criterion,
+    pub max_depth: Option<usize>,
+    pub min_samples_leaf: usize,
+    classes: Vec<L>,
+    tree: Tree,
+}
+
+impl<L: Eq + Hash + Clone> DecisionTreeClassifier<L> {
+    pub fn new() -> DecisionTreeClassifier<L> {
+        DecisionTreeClassifier {
+            criterion: Criterion::Gini,
+            max_depth: None,
+            min_samples_leaf: 1,
+            classes: Vec::new(),
+            tree: Tree::default(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], labels: &[L]) {
+        check_fit(features, labels.len());
+        let (classes, class_ids) = encode_labels(labels);
+        let target = Target::Classes(&class_ids, classes.len(), self.criterion);
+        self.tree = Tree::fit(features, &target, (0..features.len()).collect(), &self.params(), None);
+        self.classes = classes;
+    }
+
+    /// Distinct training labels in order of first appearance, the columns of `predict_proba`.
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    fn params(&self) -> Params {
+        Params {
+            max_depth: self.max_depth,
+            min_samples_leaf: self.min_samples_leaf,
+            max_features: None,
+        }
+    }
+
+    /// Predicts a label per query, `None` when the tree has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<L>> {
+        self.predict_proba(queries)
+            .iter()
+            .map(|proba| most_common(&self.classes, proba))
+            .collect()
+    }
+
+    /// Class fractions of the training samples in the leaf each query falls into.
+    pub fn predict_proba(&self, queries: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        queries.iter().map(|query| self.tree.leaf(query).to_vec()).collect()
+    }
+
+    /// Total impurity decrease contributed by every feature, normalised to sum to one.
+    pub fn feature_importances(&self) -> Vec<f32> {
+        self.tree.feature_importances()
+    }
+}
+
+impl<L: Eq + Hash + Clone + Debug> DecisionTreeClassifier<L> {
+    /// Renders the tree as nested if/else rules, naming features `x[i]` unless names are given.
+    pub fn export_rules(&self, feature_names: Option<&[&str]>) -> String {
+        self.tree.export(feature_names, &|value| {
+            let class = most_common(&self.classes, value).unwrap();
+            let share = value.iter().cloned().fold(0., f32::max);
+            format!("{:?} ({:.0}%)", class, share * 100.)
+        })
+    }
+}
+
+impl<L: Eq + Hash + Clone> Default for DecisionTreeClassifier<L> {
+    fn default() -> DecisionTreeClassifier<L> {
+        DecisionTreeClassifier::new()
+    }
+}
+
+/// CART decision tree regressor, splitting on the largest decrease in mean squared error and
+/// predicting the mean target of a leaf.
+///
+/// Stops growing like [`DecisionTreeClassifier`].
+pub struct DecisionTreeRegressor {
+    pub max_depth: Option<usize>,
+    pub min_samples_leaf: usize,
+    tree: Tree,
+}
+
+impl DecisionTreeRegressor {
+    pub fn new() -> DecisionTreeRegressor {
+        DecisionTreeRegressor {
+            max_depth: None,
+            min_samples_leaf: 1,
+            tree: Tree::default(),
+        }
+    }
+
+    pub fn fit(&mut self, features: &[Vec<f32>], targets: &[f32]) {
+        check_fit(features, targets.len());
+        let target = Target::Values(targets);
+        self.tree = Tree::fit(features, &target, (0..features.len()).collect(), &self.params(), None);
+    }
+
+    fn params(&self) -> Params {
+        Params {
+            max_depth: self.max_depth,
+            min_samples_leaf: self.min_samples_leaf,
+            max_features: None,
+        }
+    }
+
+    /// Predicts a value per query, `None` when the tree has not been fitted.
+    pub fn predict(&self, queries: &[Vec<f32>]) -> Vec<Option<f32>> {
+        queries.iter().map(|query| self.tree.leaf(query).first().copied()).collect()
+    }
+
+    /// Total impurity decrease contributed by every feature, normalised to sum to one.
+    pub fn feature_importances(&self) -> Vec<f32> {
+        self.tree.feature_importances()
+    }
+
+    /// Renders the tree as nested if/else rules, naming features `x[i]` unless names are given.
+    pub fn export_rules(&self, feature_names: Option<&[&str]>) -> String {
+        self.tree.export(feature_names, &|value| format!("{}", value[0]))
+    }
+}
+
+impl Default for DecisionTreeRegressor {
+    fn default() -> DecisionTreeRegressor {
+        DecisionTreeRegressor::new()
+    }
+}
+
+pub(crate) fn most_common<L: Clone>(classes: &[L], proba: &[f32]) -> Option<L> {
+    (0..proba.len())
+        .max_by(|&a, &b| proba[a].total_cmp(&proba[b]).then(b.cmp(&a)))
+        .map(|c| classes[c].clone())
+}
+
+// What a tree is fitted to: class ids with the number of classes, or continuous values.
+pub(crate) enum Target<'a> {
+    Classes(&'a [usize], usize, Criterion),
+    Values(&'a [f32]),
+}
+
+// Running statistics of the targets on one side of a split. Values are summed in f64, as the
+// variance of targets far from zero cancels out in f32.
+#[derive(Clone)]
+enum Stats {
+    Classes(Vec<f32>, Criterion),
+    Values { sum: f64, sum_squares: f64 },
+}
+
+impl Stats {
+    fn new(target: &Target) -> Stats {
+        match target {
+            Target::Classes(_, n_classes, criterion) => Stats::Classes(vec![0.; *n_classes], *criterion),
+            Target::Values(_) => Stats::Values { sum: 0., sum_squares: 0. },
+        }
+    }
+
+    fn add(&mut self, target: &Target, sample: usize, sign: f32) {
+        match (self, target) {
+            (Stats::Classes(counts, _), Target::Classes(ids, _, _)) => counts[ids[sample]] += sign,
+            (Stats::Values { sum, sum_squares }, Target::Values(values)) => {
+                let value = values[sample] as f64;
+                *sum += sign as f64 * value;
+                *sum_squares += sign as f64 * value.powi(2);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn impurity(&self, n: f32) -> f32 {
+        match self {
+            Stats::Classes(counts, Criterion::Gini) => 1. - counts.iter().map(|c| (c / n).powi(2)).sum::<f32>(),
+            Stats::Classes(counts, Criterion::Entropy) => {
+                -counts.iter().filter(|c| **c > 0.).map(|c| (c / n) * (c / n).log2()).sum::<f32>()
+            }
+            Stats::Values { sum, sum_squares } => {
+                let n = n as f64;
+                (sum_squares / n - (sum / n).powi(2)).max(0.) as f32
+            }
+        }
+    }
+
+    // Class fractions, or the mean value.
+    fn value(&self, n: f32) -> Vec<f32> {
+        match self {
+            Stats::Classes(counts, _) => counts.iter().map(|c| c / n).collect(),
+            Stats::Values { sum, .. } => vec![(sum / n as f64) as f32],
+        }
+    }
+}
+
+// When a tree stops growing, and how many randomly chosen features each split considers.
+pub(crate) struct Params {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) min_samples_leaf: usize,
+    pub(crate) max_features: Option<usize>,
+}
+
+enum Node {
+    Leaf(Vec<f32>),
+    // Samples with `feature` at most `threshold` go left
+    Split {
+        feature: usize,
+        threshold: f32,
+        left: usize,
+        right: usize,
+    },
+}
+
+#[derive(Default)]
+pub(crate) struct Tree {
+    nodes: Vec<Node>,
+    importances: Vec<f32>,
+}
+
+// What is left to write when exporting a tree: a node at some depth, or a line closing a split.
+enum Export {
+    Node(usize, usize),
+    Line(String),
+}
+
+struct Split {
+    feature: usize,
+    threshold: f32,
+    impurity_decrease: f32,
+    left: Vec<usize>,
+    right: Vec<usize>,
+}
+
+impl Tree {
+    // Grows a tree on the given samples, which may repeat. Feature subsampling needs an `rng`.
+    pub(crate) fn fit(features: &[Vec<f32>], target: &Target, samples: Vec<usize>, params: &Params, mut rng: Option<&mut StdRng>) -> Tree {
+        let mut tree = Tree {
+            nodes: Vec::new(),
+            importances: vec![0.; features.first().map_or(0, |row| row.len())],
+        };
+        if samples.is_empty() {
+            return tree;
+        }
+
+        // Nodes still to grow, the left child popped first so nodes are visited depth-first
+        tree.nodes.push(Node::Leaf(Vec::new()));
+        let mut stack = vec![(0, samples, 0)];
+        while let Some((node, samples, depth)) = stack.pop() {
+            let (value, split) = tree.grow(features, target, &samples, depth, params, &mut rng);
+            tree.nodes[node] = Node::Leaf(value);
+            if let Some(split) = split {
+                let (left, right) = (tree.nodes.len(), tree.nodes.len() + 1);
+                tree.nodes.push(Node::Leaf(Vec::new()));
+                tree.nodes.push(Node::Leaf(Vec::new()));
+                tree.nodes[node] = Node::Split {
+                    feature: split.feature,
+                    threshold: split.threshold,
+                    left,
+                    right,
+                };
+                stack.push((right, split.right, depth + 1));
+                stack.push((left, split.left, depth + 1));
+            }
+        }
+        tree
+    }
+
+    // Leaf value of the given samples, and how to split them further if they should be.
+    fn grow(
+        &mut self,
+        features: &[Vec<f32>],
+        target: &Target,
+        samples: &[usize],
+        depth: usize,
+        params: &Params,
+        rng: &mut Option<&mut StdRng>,
+    ) -> (Vec<f32>, Option<Split>) {
+        let mut stats = Stats::new(target);
+        samples.iter().for_each(|&s| stats.add(target, s, 1.));
+        let n = samples.len() as f32;
+        let impurity = stats.impurity(n);
+
+        let value = stats.value(n);
+        let min_samples_leaf = params.min_samples_leaf.max(1);
+        if impurity <= 0. || params.max_depth.is_some_and(|max| depth >= max) || samples.len() < 2 * min_samples_leaf {
+            return (value, None);
+        }
+
+        let n_features = self.importances.len();
+        let candidates = match (params.max_features, rng.as_mut()) {
+            (Some(max_features), Some(rng)) if max_features < n_features => {
+                let mut candidates = rand::seq::index::sample(&mut **rng, n_features, max_features.max(1)).into_vec();
+                candidates.sort_unstable();
+                candidates
+            }
+            _ => (0..n_features).collect(),
+        };
+        let split = Tree::best_split(features, target, samples, &candidates, &stats, impurity, min_samples_leaf);
+        if let Some(split) = &split {
+            self.importances[split.feature] += split.impurity_decrease;
+        }
+        (value, split)
+    }
+
+    // Picks the candidate feature and threshold whose children have the lowest weighted impurity.
+    fn best_split(
+        features: &[Vec<f32>],
+        target: &Target,
+        samples: &[usize],
+        candidates: &[usize],
+        stats: &Stats,
+        impurity: f32,
+        min_samples_leaf: usize,
+    ) -> Option<Split> {
+        let n = samples.len();
+        let best = candidates
+            .iter()
+            .filter_map(|&feature| {
+                Tree::best_threshold(features, feature, target, samples, stats, min_samples_leaf)
+                    .map(|(threshold, children)| (feature, threshold, children))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+
+        match best {
+            Some((feature, threshold, children)) if children < impurity => {
+                let (left, right): (Vec<usize>, Vec<usize>) = samples.iter().partition(|&&s| features[s][feature] <= threshold);
+                if left.is_empty() || right.is_empty() {
+                    return None;
+                }
+                Some(Split {
+                    feature,
+                    threshold,
+                    impurity_decrease: n as f32 * (impurity - children),
+                    left,
+                    right,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // Sweeps the samples in order of `feature`, moving them from the right child to the left one.
+    fn best_threshold(
+        features: &[Vec<f32>],
+        feature: usize,
+        target: &Target,
+        samples: &[usize],
+        stats: &Stats,
+        min_samples_leaf: usize,
+    ) -> Option<(f32, f32)> {
+        let n = samples.len();
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|&a, &b| features[a][feature].total_cmp(&features[b][feature]));
+
+        let mut best: Option<(f32, f32)> = None;
+        let mut left = Stats::new(target);
+        let mut right = stats.clone();
+        for (i, pair) in (1..n).zip(sorted.windows(2)) {
+            left.add(target, pair[0], 1.);
+            right.add(target, pair[0], -1.);
+            let (low, high) = (features[pair[0]][feature], features[pair[1]][feature]);
+            if low == high || i < min_samples_leaf || n - i < min_samples_leaf {
+                continue;
+            }
+
+            let children = (i as f32 * left.impurity(i as f32) + (n - i) as f32 * right.impurity((n - i) as f32)) / n as f32;
+            if best.is_none_or(|(_, best_children)| children < best_children) {
+                // Between adjacent floats the midpoint rounds up to `high`, which would send every
+                // sample left
+                let midpoint = low + (high - low) / 2.;
+                best = Some((if midpoint < high { midpoint } else { low }, children));
+            }
+        }
+        best
+    }
+
+    // Value of the leaf `query` ends up in, empty for an unfitted tree.
+    pub(crate) fn leaf(&self, query: &[f32]) -> &[f32] {
+        let mut node = 0;
+        loop {
+            match self.nodes.get(node) {
+                None => return &[],
+                Some(Node::Leaf(value)) => return value,
+                Some(Node::Split {
+                    feature,
+                    threshold,
+                    left,
+                    right,
+                }) => {
+                    node = if query[*feature] <= *threshold { *left } else { *right };
+                }
+            }
+        }
+    }
+
+    pub(crate) fn feature_importances(&self) -> Vec<f32> {
+        let total: f32 = self.importances.iter().sum();
+        if total > 0. {
+            self.importances.iter().map(|i| i / total).collect()
+        } else {
+            self.importances.clone()
+        }
+    }
+
+    // Writes the nodes depth-first from an explicit stack, like `fit` grows them.
+    fn export(&self, feature_names: Option<&[&str]>, leaf: &dyn Fn(&[f32]) -> String) -> String {
+        let mut rules = String::new();
+        let mut stack = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![Export::Node(0, 0)]
+        };
+        while let Some(item) = stack.pop() {
+            let (node, depth) = match item {
+                Export::Node(node, depth) => (node, depth),
+                Export::Line(line) => {
+                    rules.push_str(&line);
+                    continue;
+                }
+            };
+            let indent = "    ".repeat(depth);
+            match &self.nodes[node] {
+                Node::Leaf(value) => writeln!(rules, "{}{}", indent, leaf(value)).unwrap(),
+                Node::Split {
+                    feature,
+                    threshold,
+                    left,
+                    right,
+                } => {
+                    let name = match feature_names {
+                        Some(names) => names[*feature].to_string(),
+                        None => format!("x[{}]", feature),
+                    };
+                    writeln!(rules, "{}if {} <= {} {{", indent, name, threshold).unwrap();
+                    stack.push(Export::Line(format!("{}}}\n", indent)));
+                    stack.push(Export::Node(*right, depth + 1));
+                    stack.push(Export::Line(format!("{}}} else {{\n", indent)));
+                    stack.push(Export::Node(*left, depth + 1));
+                }
+            }
+        }
+        rules
+    }
+}
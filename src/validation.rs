@@ -0,0 +1,40 @@
+use std::hash::Hash;
+
+/// Distinct labels in order of first appearance, and the position of every label among them.
+pub(crate) fn encode_labels<L: Eq + Hash + Clone>(labels: &[L]) -> (Vec<L>, Vec<usize>) {
+    let mut classes: Vec<L> = Vec::new();
+    let class_ids = labels
+        .iter()
+        .map(|label| match classes.iter().position(|c| c == label) {
+            Some(id) => id,
+            None => {
+                classes.push(label.clone());
+                classes.len() - 1
+            }
+        })
+        .collect();
+    (classes, class_ids)
+}
+
+/// Panics unless every row has as many features as the first.
+pub(crate) fn check_rows(features: &[Vec<f32>]) {
+    if let Some(row) = features.iter().find(|row| row.len() != features[0].len()) {
+        panic!(
+            "Rows have different numbers of features, got {} and {}",
+            features[0].len(),
+            row.len()
+        );
+    }
+}
+
+/// Panics unless there is a target per row and every row has as many features as the first.
+pub(crate) fn check_fit(features: &[Vec<f32>], n_targets: usize) {
+    if features.len() != n_targets {
+        panic!(
+            "Features and targets have different lengths, got {} and {}",
+            features.len(),
+            n_targets
+        );
+    }
+    check_rows(features);
+}